@@ -6,11 +6,53 @@
 
 // Enums
 
+// Alpha expects straight rather than premultiplied color, Replace skips
+// blending entirely, and Custom uses the given blend components.
+typedef enum {
+    taca_Blend_Alpha,
+    taca_Blend_Premultiplied,
+    taca_Blend_Additive,
+    taca_Blend_Multiply,
+    taca_Blend_Replace,
+    taca_Blend_Custom,
+} taca_Blend;
+
+typedef enum {
+    taca_BlendFactor_Zero,
+    taca_BlendFactor_One,
+    taca_BlendFactor_Src,
+    taca_BlendFactor_OneMinusSrc,
+    taca_BlendFactor_SrcAlpha,
+    taca_BlendFactor_OneMinusSrcAlpha,
+    taca_BlendFactor_Dst,
+    taca_BlendFactor_OneMinusDst,
+    taca_BlendFactor_DstAlpha,
+    taca_BlendFactor_OneMinusDstAlpha,
+    taca_BlendFactor_SrcAlphaSaturated,
+} taca_BlendFactor;
+
+// Min and Max ignore factors.
+typedef enum {
+    taca_BlendOperation_Add,
+    taca_BlendOperation_Subtract,
+    taca_BlendOperation_ReverseSubtract,
+    taca_BlendOperation_Min,
+    taca_BlendOperation_Max,
+} taca_BlendOperation;
+
 typedef enum {
     taca_BufferKind_Vertex,
     taca_BufferKind_Index,
 } taca_BufferKind;
 
+typedef enum {
+    taca_ErrorCode_None,
+    taca_ErrorCode_BadHandle,
+    taca_ErrorCode_BadMemory,
+    taca_ErrorCode_BadValue,
+    taca_ErrorCode_NotReady,
+} taca_ErrorCode;
+
 // Note that size in C requires C23.
 typedef enum /* : uint32_t */ {
     taca_EventKind_Frame,
@@ -18,6 +60,9 @@ typedef enum /* : uint32_t */ {
     taca_EventKind_TasksDone,
     taca_EventKind_Press,
     taca_EventKind_Release,
+    taca_EventKind_Text,
+    taca_EventKind_TaskFailed,
+    taca_EventKind_TaskDone,
 } taca_EventKind;
 
 typedef enum {
@@ -30,6 +75,35 @@ typedef enum {
     taca_Key_Escape,
 } taca_Key;
 
+// Auto clears on a target's first pass in a frame, to black for color, and
+// loads what was already drawn after that.
+typedef enum {
+    taca_LoadOp_Auto,
+    taca_LoadOp_Clear,
+    taca_LoadOp_Load,
+} taca_LoadOp;
+
+// Auto streams sounds of 30 seconds or more, decoding them as they play rather
+// than holding all samples in memory.
+typedef enum {
+    taca_SoundDecodeMode_Auto,
+    taca_SoundDecodeMode_Static,
+    taca_SoundDecodeMode_Streaming,
+} taca_SoundDecodeMode;
+
+typedef enum {
+    taca_SoundEffectKind_Filter,
+    taca_SoundEffectKind_Reverb,
+    taca_SoundEffectKind_Delay,
+} taca_SoundEffectKind;
+
+typedef enum {
+    taca_SoundFilterMode_LowPass,
+    taca_SoundFilterMode_BandPass,
+    taca_SoundFilterMode_HighPass,
+    taca_SoundFilterMode_Notch,
+} taca_SoundFilterMode;
+
 typedef enum {
     taca_SoundRateKind_Semitones,
     taca_SoundRateKind_Factor,
@@ -40,11 +114,29 @@ typedef enum {
     taca_SoundVolumeKind_Factor,
 } taca_SoundVolumeKind;
 
+typedef enum {
+    taca_StorageResult_Ok,
+    taca_StorageResult_BadKey,
+    taca_StorageResult_OverQuota,
+    taca_StorageResult_Failed,
+} taca_StorageResult;
+
 typedef enum {
     taca_Step_Vertex,
     taca_Step_Instance,
 } taca_Step;
 
+typedef enum {
+    taca_TaskKind_Image,
+    taca_TaskKind_Sound,
+} taca_TaskKind;
+
+typedef enum {
+    taca_TaskStatus_Pending,
+    taca_TaskStatus_Ready,
+    taca_TaskStatus_Failed,
+} taca_TaskStatus;
+
 typedef enum {
     taca_TextAlignX_Left,
     taca_TextAlignX_Center,
@@ -58,13 +150,22 @@ typedef enum {
     taca_TextAlignY_Bottom,
 } taca_TextAlignY;
 
+// Bytes per pixel are 4 for Rgba8 and 1 for R8.
+typedef enum {
+    taca_TextureFormat_Rgba8,
+    taca_TextureFormat_R8,
+} taca_TextureFormat;
+
 // Handles
 
 typedef size_t taca_Buffer;
 typedef size_t taca_Pipeline;
 typedef size_t taca_Shader;
 typedef size_t taca_Sound;
+typedef size_t taca_Texture;
 typedef size_t taca_SoundPlay;
+typedef size_t taca_SoundStream;
+typedef size_t taca_SoundTrack;
 
 // Supports
 
@@ -79,8 +180,14 @@ typedef unsigned char taca_byte;
 // clang-format on
 
 taca_span_define(taca_ByteSpan, taca_byte);
+taca_span_define(taca_FloatSpan, float);
 taca_span_define(taca_StringView, char);
 
+typedef struct {
+    taca_byte* data;
+    size_t size;
+} taca_ByteBuffer;
+
 typedef struct {
     float x;
     float y;
@@ -95,6 +202,20 @@ typedef struct {
 
 taca_span_define(taca_AttributeInfoSpan, taca_AttributeInfo);
 
+typedef struct {
+    taca_BlendFactor src_factor;
+    taca_BlendFactor dst_factor;
+    taca_BlendOperation operation;
+} taca_BlendComponent;
+
+// All zeros means straight alpha.
+typedef struct {
+    taca_Blend preset;
+    // Only used for Custom.
+    taca_BlendComponent color;
+    taca_BlendComponent alpha;
+} taca_BlendInfo;
+
 typedef struct {
     size_t first_attribute;
     taca_Step step;
@@ -115,6 +236,17 @@ typedef struct {
     uint32_t modifiers;
 } taca_KeyEvent;
 
+typedef struct {
+    // From taca_texture_target_new, or 0 for the window. Passes can't sample
+    // their own target.
+    taca_Texture target;
+    // Linear rgba used when color_load is Clear.
+    float clear_color[4];
+    taca_LoadOp color_load;
+    // Depth always clears to 1.
+    taca_LoadOp depth_load;
+} taca_PassInfo;
+
 typedef struct {
     taca_StringView entry;
     taca_Shader shader;
@@ -126,6 +258,7 @@ typedef struct {
     taca_PipelineShaderInfo vertex;
     taca_AttributeInfoSpan vertex_attributes;
     taca_BufferInfoSpan vertex_buffers;
+    taca_BlendInfo blend;
 } taca_PipelineInfo;
 
 typedef struct {
@@ -135,8 +268,80 @@ typedef struct {
     taca_SoundRateKind rate_kind;
     float volume;
     taca_SoundVolumeKind volume_kind;
+    bool looping;
+    // Loop region in seconds, where an end not after the start means the end
+    // of the sound.
+    float loop_start;
+    float loop_end;
+    // From -1 for left through 1 for right.
+    float panning;
+    // Track 0 is the main track.
+    taca_SoundTrack track;
 } taca_SoundPlayInfo;
 
+// Zero values keep defaults, except that delay_feedback uses NAN, and fields
+// for other kinds are ignored.
+typedef struct {
+    taca_SoundEffectKind kind;
+    // From 0 for dry through 1 for wet.
+    float mix;
+    taca_SoundFilterMode filter_mode;
+    // In hertz.
+    float filter_cutoff;
+    float filter_resonance;
+    float reverb_feedback;
+    float reverb_damping;
+    // In seconds.
+    float delay_time;
+    // In decibels, where 0 repeats forever and NAN keeps the default of -6.
+    float delay_feedback;
+} taca_SoundEffectInfo;
+
+taca_span_define(taca_SoundEffectInfoSpan, taca_SoundEffectInfo);
+
+// All zeros until decoding finishes.
+typedef struct {
+    // In seconds.
+    float duration;
+    // The file's rate on native, but browsers resample on decode, so web gives
+    // the audio output rate instead.
+    uint32_t sample_rate;
+    uint32_t channels;
+} taca_SoundInfo;
+
+typedef struct {
+    uint32_t sample_rate;
+    // Either 1 for mono or 2 for interleaved stereo.
+    uint32_t channels;
+    // Frames buffered on the host, where 0 means a quarter second.
+    uint32_t capacity;
+    // Track 0 is the main track.
+    taca_SoundTrack track;
+} taca_SoundStreamInfo;
+
+typedef struct {
+    // Track 0 is the main track.
+    taca_SoundTrack parent;
+    float volume;
+    taca_SoundVolumeKind volume_kind;
+    taca_SoundEffectInfoSpan effects;
+} taca_SoundTrackInfo;
+
+// Handles here are textures for images.
+typedef struct {
+    taca_TaskKind kind;
+    size_t handle;
+    taca_TaskStatus status;
+} taca_TaskEvent;
+
+// Pixels go in rows from the top, where empty pixels start all zeros.
+typedef struct {
+    size_t width;
+    size_t height;
+    taca_TextureFormat format;
+    taca_ByteSpan pixels;
+} taca_TextureNewInfo;
+
 typedef struct {
     taca_Vec2 pointer;
     uint32_t press;
@@ -151,6 +356,10 @@ extern "C" {
 
 // clang-format off
 
+// Dropped handles become invalid, even if a later handle reuses the slot.
+__attribute__((import_name("taca_buffer_drop")))
+void taca_buffer_drop(taca_Buffer buffer);
+
 __attribute__((import_name("taca_buffer_new")))
 taca_Buffer taca_buffer_new(taca_BufferKind kind, taca_ByteSpan bytes);
 
@@ -171,32 +380,162 @@ void taca_draw(
     uint32_t instance_count
 );
 
+// Returns the error from the most recent failed call since last checked, then
+// clears it. Failed calls otherwise return zero values.
+__attribute__((import_name("taca_error_last")))
+taca_ErrorCode taca_error_last(void);
+
 __attribute__((import_name("taca_key_event")))
 taca_KeyEvent taca_key_event(void);
 
-__attribute__((import_name("taca_pipeline_new")))
+// Ends any current pass, after which pipelines and buffers need applying again.
+__attribute__((import_name("taca_pass_begin")))
+void taca_pass_begin(const taca_PassInfo* info);
+
+__attribute__((import_name("taca_pipeline_drop")))
+void taca_pipeline_drop(taca_Pipeline pipeline);
+
+// Imports taca_pipeline_new2 for the current info layout, where
+// taca_pipeline_new takes only the fields through vertex_buffers.
+__attribute__((import_name("taca_pipeline_new2")))
 taca_Pipeline taca_pipeline_new(const taca_PipelineInfo* info);
 
 // __attribute__((import_module("taca"), import_name("print")))
 __attribute__((import_name("taca_print")))
 void taca_print(taca_StringView text);
 
+__attribute__((import_name("taca_shader_drop")))
+void taca_shader_drop(taca_Shader shader);
+
 __attribute__((import_name("taca_shader_new")))
 taca_Shader taca_shader_new(taca_ByteSpan bytes);
 
 __attribute__((import_name("taca_sound_decode")))
 taca_Sound taca_sound_decode(taca_ByteSpan bytes);
 
-__attribute__((import_name("taca_sound_play")))
+__attribute__((import_name("taca_sound_decode_mode")))
+taca_Sound taca_sound_decode_mode(taca_ByteSpan bytes, taca_SoundDecodeMode mode);
+
+// Sounds already playing keep going.
+__attribute__((import_name("taca_sound_drop")))
+void taca_sound_drop(taca_Sound sound);
+
+__attribute__((import_name("taca_sound_info")))
+taca_SoundInfo taca_sound_info(taca_Sound sound);
+
+// Panning goes from -1 for left through 1 for right. Durations are in seconds
+// for a linear change. Plays that have finished ignore changes.
+__attribute__((import_name("taca_sound_panning_update")))
+void taca_sound_panning_update(taca_SoundPlay play, float panning, float duration);
+
+__attribute__((import_name("taca_sound_pause")))
+void taca_sound_pause(taca_SoundPlay play, float duration);
+
+// Returns 0 if audio is unavailable. Imports taca_sound_play2 for the current
+// info layout, where taca_sound_play takes only the fields through volume_kind.
+__attribute__((import_name("taca_sound_play2")))
 // TODO Without explicit pointer, and if only one field, this gets passed as the field value.
 taca_SoundPlay taca_sound_play(const taca_SoundPlayInfo* info);
 
+__attribute__((import_name("taca_sound_rate_update")))
+void taca_sound_rate_update(
+    taca_SoundPlay play, float rate, taca_SoundRateKind rate_kind, float duration
+);
+
+__attribute__((import_name("taca_sound_resume")))
+void taca_sound_resume(taca_SoundPlay play, float duration);
+
+__attribute__((import_name("taca_sound_status")))
+taca_TaskStatus taca_sound_status(taca_Sound sound);
+
+// Copies as much of any failure message as fits and returns its full size.
+__attribute__((import_name("taca_sound_status_message")))
+size_t taca_sound_status_message(taca_Sound sound, taca_ByteBuffer buffer);
+
+__attribute__((import_name("taca_sound_stop")))
+void taca_sound_stop(taca_SoundPlay play, float duration);
+
+// Samples already written still play out.
+__attribute__((import_name("taca_sound_stream_drop")))
+void taca_sound_stream_drop(taca_SoundStream stream);
+
+// Starts playing right away, with silence until samples are written. Returns 0
+// if audio is unavailable, in which case writes do nothing.
+__attribute__((import_name("taca_sound_stream_new")))
+taca_SoundStream taca_sound_stream_new(const taca_SoundStreamInfo* info);
+
+// Returns how many frames fit in the buffer right now.
+__attribute__((import_name("taca_sound_stream_space")))
+size_t taca_sound_stream_space(taca_SoundStream stream);
+
+// Writes as many whole frames as fit and returns how many were written.
+__attribute__((import_name("taca_sound_stream_write")))
+size_t taca_sound_stream_write(taca_SoundStream stream, taca_FloatSpan samples);
+
+__attribute__((import_name("taca_sound_track_drop")))
+void taca_sound_track_drop(taca_SoundTrack track);
+
+// Returns 0 if audio is unavailable, which plays treat as the main track.
+__attribute__((import_name("taca_sound_track_new")))
+taca_SoundTrack taca_sound_track_new(const taca_SoundTrackInfo* info);
+
+__attribute__((import_name("taca_sound_track_volume_update")))
+void taca_sound_track_volume_update(
+    taca_SoundTrack track, float volume, taca_SoundVolumeKind volume_kind, float duration
+);
+
+__attribute__((import_name("taca_sound_volume_update")))
+void taca_sound_volume_update(
+    taca_SoundPlay play, float volume, taca_SoundVolumeKind volume_kind, float duration
+);
+
+// Copies as much of the value as fits and returns its full size, or 0 if none.
+__attribute__((import_name("taca_storage_read")))
+size_t taca_storage_read(taca_StringView key, taca_ByteBuffer buffer);
+
+// Keys are 1 to 125 bytes, and writing empty bytes removes the key.
+__attribute__((import_name("taca_storage_write")))
+taca_StorageResult taca_storage_write(taca_StringView key, taca_ByteSpan bytes);
+
+// Describes the task for the current task event.
+__attribute__((import_name("taca_task_event")))
+taca_TaskEvent taca_task_event(void);
+
 __attribute__((import_name("taca_text_align")))
 void taca_text_align(taca_TextAlignX x, taca_TextAlignY y);
 
 __attribute__((import_name("taca_text_draw")))
 void taca_text_draw(taca_StringView text, float x, float y);
 
+__attribute__((import_name("taca_texture_drop")))
+void taca_texture_drop(taca_Texture texture);
+
+__attribute__((import_name("taca_texture_new")))
+taca_Texture taca_texture_new(const taca_TextureNewInfo* info);
+
+__attribute__((import_name("taca_texture_status")))
+taca_TaskStatus taca_texture_status(taca_Texture texture);
+
+// Copies as much of any failure message as fits and returns its full size.
+__attribute__((import_name("taca_texture_status_message")))
+size_t taca_texture_status_message(taca_Texture texture, taca_ByteBuffer buffer);
+
+// Makes a texture for passes to render into, which can then be sampled in later
+// passes but not in its own.
+__attribute__((import_name("taca_texture_target_new")))
+taca_Texture taca_texture_target_new(size_t width, size_t height);
+
+// Replaces a rectangle with tightly packed pixels in the texture's format.
+__attribute__((import_name("taca_texture_update")))
+void taca_texture_update(
+    taca_Texture texture,
+    taca_ByteSpan pixels,
+    size_t x,
+    size_t y,
+    size_t width,
+    size_t height
+);
+
 __attribute__((import_name("taca_title_update")))
 void taca_title_update(taca_StringView text);
 
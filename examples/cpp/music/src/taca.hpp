@@ -10,6 +10,37 @@ namespace taca {
 
 // Enums, redone as enum struct
 
+enum struct Blend : std::uint32_t {
+    Alpha = taca_Blend_Alpha,
+    Premultiplied = taca_Blend_Premultiplied,
+    Additive = taca_Blend_Additive,
+    Multiply = taca_Blend_Multiply,
+    Replace = taca_Blend_Replace,
+    Custom = taca_Blend_Custom,
+};
+
+enum struct BlendFactor : std::uint32_t {
+    Zero = taca_BlendFactor_Zero,
+    One = taca_BlendFactor_One,
+    Src = taca_BlendFactor_Src,
+    OneMinusSrc = taca_BlendFactor_OneMinusSrc,
+    SrcAlpha = taca_BlendFactor_SrcAlpha,
+    OneMinusSrcAlpha = taca_BlendFactor_OneMinusSrcAlpha,
+    Dst = taca_BlendFactor_Dst,
+    OneMinusDst = taca_BlendFactor_OneMinusDst,
+    DstAlpha = taca_BlendFactor_DstAlpha,
+    OneMinusDstAlpha = taca_BlendFactor_OneMinusDstAlpha,
+    SrcAlphaSaturated = taca_BlendFactor_SrcAlphaSaturated,
+};
+
+enum struct BlendOperation : std::uint32_t {
+    Add = taca_BlendOperation_Add,
+    Subtract = taca_BlendOperation_Subtract,
+    ReverseSubtract = taca_BlendOperation_ReverseSubtract,
+    Min = taca_BlendOperation_Min,
+    Max = taca_BlendOperation_Max,
+};
+
 enum struct BufferKind : std::uint32_t {
     Vertex = taca_BufferKind_Vertex,
     Index = taca_BufferKind_Index,
@@ -21,6 +52,9 @@ enum struct EventKind : std::uint32_t {
     TasksDone = taca_EventKind_TasksDone,
     Press = taca_EventKind_Press,
     Release = taca_EventKind_Release,
+    Text = taca_EventKind_Text,
+    TaskFailed = taca_EventKind_TaskFailed,
+    TaskDone = taca_EventKind_TaskDone,
 };
 
 enum struct Key : std::uint32_t {
@@ -33,6 +67,31 @@ enum struct Key : std::uint32_t {
     Escape = taca_Key_Escape,
 };
 
+enum struct LoadOp : std::uint32_t {
+    Auto = taca_LoadOp_Auto,
+    Clear = taca_LoadOp_Clear,
+    Load = taca_LoadOp_Load,
+};
+
+enum struct SoundDecodeMode : std::uint32_t {
+    Auto = taca_SoundDecodeMode_Auto,
+    Static = taca_SoundDecodeMode_Static,
+    Streaming = taca_SoundDecodeMode_Streaming,
+};
+
+enum struct SoundEffectKind : std::uint32_t {
+    Filter = taca_SoundEffectKind_Filter,
+    Reverb = taca_SoundEffectKind_Reverb,
+    Delay = taca_SoundEffectKind_Delay,
+};
+
+enum struct SoundFilterMode : std::uint32_t {
+    LowPass = taca_SoundFilterMode_LowPass,
+    BandPass = taca_SoundFilterMode_BandPass,
+    HighPass = taca_SoundFilterMode_HighPass,
+    Notch = taca_SoundFilterMode_Notch,
+};
+
 enum struct SoundRateKind : std::uint32_t {
     Semitones = taca_SoundRateKind_Semitones,
     Factor = taca_SoundRateKind_Factor,
@@ -48,6 +107,17 @@ enum struct Step : std::uint32_t {
     Instance = taca_Step_Instance,
 };
 
+enum struct TaskKind : std::uint32_t {
+    Image = taca_TaskKind_Image,
+    Sound = taca_TaskKind_Sound,
+};
+
+enum struct TaskStatus : std::uint32_t {
+    Pending = taca_TaskStatus_Pending,
+    Ready = taca_TaskStatus_Ready,
+    Failed = taca_TaskStatus_Failed,
+};
+
 enum struct TextAlignX {
     Left = taca_TextAlignX_Left,
     Center = taca_TextAlignX_Center,
@@ -61,6 +131,11 @@ enum struct TextAlignY {
     Bottom = taca_TextAlignY_Bottom,
 };
 
+enum struct TextureFormat : std::uint32_t {
+    Rgba8 = taca_TextureFormat_Rgba8,
+    R8 = taca_TextureFormat_R8,
+};
+
 // Aliases
 
 using AttributeInfo = taca_AttributeInfo;
@@ -68,7 +143,12 @@ using Buffer = taca_Buffer;
 using Pipeline = taca_Pipeline;
 using Shader = taca_Shader;
 using Sound = taca_Sound;
+using SoundInfo = taca_SoundInfo;
 using SoundPlay = taca_SoundPlay;
+using SoundStream = taca_SoundStream;
+using SoundStreamInfo = taca_SoundStreamInfo;
+using SoundTrack = taca_SoundTrack;
+using Texture = taca_Texture;
 using WindowState = taca_WindowState;
 using Vec2 = taca_Vec2;
 
@@ -76,6 +156,18 @@ using ByteSpan = std::span<const std::byte>;
 
 // Structs with redone insides
 
+struct BlendComponent {
+    BlendFactor src_factor;
+    BlendFactor dst_factor;
+    BlendOperation operation;
+};
+
+struct BlendInfo {
+    Blend preset;
+    BlendComponent color;
+    BlendComponent alpha;
+};
+
 struct BufferInfo {
     std::size_t first_attribute;
     Step step;
@@ -93,6 +185,13 @@ struct KeyEvent {
     std::uint32_t modifiers;
 };
 
+struct PassInfo {
+    Texture target;
+    std::array<float, 4> clear_color;
+    LoadOp color_load;
+    LoadOp depth_load;
+};
+
 struct PipelineShaderInfo {
     std::string_view entry;
     Shader shader;
@@ -104,6 +203,7 @@ struct PipelineInfo {
     PipelineShaderInfo vertex;
     std::span<AttributeInfo> vertex_attributes;
     std::span<BufferInfo> vertex_buffers;
+    BlendInfo blend;
 };
 
 struct SoundPlayInfo {
@@ -113,6 +213,44 @@ struct SoundPlayInfo {
     SoundRateKind rate_kind;
     float volume;
     SoundVolumeKind volume_kind;
+    bool looping;
+    float loop_start;
+    float loop_end;
+    float panning;
+    SoundTrack track;
+};
+
+struct SoundEffectInfo {
+    SoundEffectKind kind;
+    float mix;
+    SoundFilterMode filter_mode;
+    float filter_cutoff;
+    float filter_resonance;
+    float reverb_feedback;
+    float reverb_damping;
+    float delay_time;
+    // In decibels, where NAN keeps the default of -6.
+    float delay_feedback;
+};
+
+struct SoundTrackInfo {
+    SoundTrack parent;
+    float volume;
+    SoundVolumeKind volume_kind;
+    std::span<const SoundEffectInfo> effects;
+};
+
+struct TaskEvent {
+    TaskKind kind;
+    std::size_t handle;
+    TaskStatus status;
+};
+
+struct TextureNewInfo {
+    std::size_t width;
+    std::size_t height;
+    TextureFormat format;
+    ByteSpan pixels;
 };
 
 // Helpers
@@ -164,6 +302,10 @@ auto key_event() -> KeyEvent {
     return reinterpret_cast<KeyEvent&>(event);
 }
 
+auto pass_begin(const PassInfo& info) -> void {
+    taca_pass_begin(reinterpret_cast<const taca_PassInfo*>(&info));
+}
+
 auto pipeline_new(PipelineInfo info) -> Pipeline {
     // All this for fear that std::span field order might be unpromised.
     auto out = taca_PipelineInfo{
@@ -187,6 +329,7 @@ auto pipeline_new(PipelineInfo info) -> Pipeline {
                 ),
                 .size = info.vertex_buffers.size(),
             },
+        .blend = reinterpret_cast<const taca_BlendInfo&>(info.blend),
     };
     return taca_pipeline_new(&out);
 }
@@ -203,11 +346,110 @@ auto sound_decode(ByteSpan bytes) -> Sound {
     return taca_sound_decode(to_taca(bytes));
 }
 
+auto sound_decode(ByteSpan bytes, SoundDecodeMode mode) -> Sound {
+    return taca_sound_decode_mode(
+        to_taca(bytes), static_cast<taca_SoundDecodeMode>(mode)
+    );
+}
+
+auto sound_info(Sound sound) -> SoundInfo {
+    return taca_sound_info(sound);
+}
+
+auto sound_panning_update(SoundPlay play, float panning, float duration = 0)
+    -> void {
+    taca_sound_panning_update(play, panning, duration);
+}
+
+auto sound_pause(SoundPlay play, float duration = 0) -> void {
+    taca_sound_pause(play, duration);
+}
+
 auto sound_play(const SoundPlayInfo& info) -> SoundPlay {
     auto out = reinterpret_cast<const taca_SoundPlayInfo&>(info);
     return taca_sound_play(&out);
 }
 
+auto sound_rate_update(
+    SoundPlay play, float rate, SoundRateKind rate_kind, float duration = 0
+) -> void {
+    taca_sound_rate_update(
+        play, rate, static_cast<taca_SoundRateKind>(rate_kind), duration
+    );
+}
+
+auto sound_resume(SoundPlay play, float duration = 0) -> void {
+    taca_sound_resume(play, duration);
+}
+
+auto sound_status(Sound sound) -> TaskStatus {
+    return static_cast<TaskStatus>(taca_sound_status(sound));
+}
+
+auto sound_stop(SoundPlay play, float duration = 0) -> void {
+    taca_sound_stop(play, duration);
+}
+
+auto sound_stream_drop(SoundStream stream) -> void {
+    taca_sound_stream_drop(stream);
+}
+
+auto sound_stream_new(const SoundStreamInfo& info) -> SoundStream {
+    return taca_sound_stream_new(&info);
+}
+
+auto sound_stream_space(SoundStream stream) -> std::size_t {
+    return taca_sound_stream_space(stream);
+}
+
+auto sound_stream_write(SoundStream stream, std::span<const float> samples)
+    -> std::size_t {
+    return taca_sound_stream_write(stream, {samples.data(), samples.size()});
+}
+
+auto sound_track_drop(SoundTrack track) -> void {
+    taca_sound_track_drop(track);
+}
+
+auto sound_track_new(const SoundTrackInfo& info) -> SoundTrack {
+    auto out = taca_SoundTrackInfo{
+        .parent = info.parent,
+        .volume = info.volume,
+        .volume_kind = static_cast<taca_SoundVolumeKind>(info.volume_kind),
+        .effects =
+            {
+                .data = reinterpret_cast<const taca_SoundEffectInfo*>(
+                    info.effects.data()
+                ),
+                .size = info.effects.size(),
+            },
+    };
+    return taca_sound_track_new(&out);
+}
+
+auto sound_track_volume_update(
+    SoundTrack track, float volume, SoundVolumeKind volume_kind,
+    float duration = 0
+) -> void {
+    taca_sound_track_volume_update(
+        track, volume, static_cast<taca_SoundVolumeKind>(volume_kind), duration
+    );
+}
+
+auto sound_volume_update(
+    SoundPlay play, float volume, SoundVolumeKind volume_kind,
+    float duration = 0
+) -> void {
+    taca_sound_volume_update(
+        play, volume, static_cast<taca_SoundVolumeKind>(volume_kind), duration
+    );
+}
+
+auto task_event() -> TaskEvent {
+    auto event = taca_task_event();
+    return reinterpret_cast<TaskEvent&>(event);
+}
+
 auto text_align(TextAlignX x, TextAlignY y) -> void {
     taca_text_align(
         static_cast<taca_TextAlignX>(x),
@@ -219,6 +461,31 @@ auto text_draw(std::string_view bytes, float x, float y) -> void {
     taca_text_draw(to_taca(bytes), x, y);
 }
 
+auto texture_new(const TextureNewInfo& info) -> Texture {
+    auto out = taca_TextureNewInfo{
+        .width = info.width,
+        .height = info.height,
+        .format = static_cast<taca_TextureFormat>(info.format),
+        .pixels = to_taca(info.pixels),
+    };
+    return taca_texture_new(&out);
+}
+
+auto texture_status(Texture texture) -> TaskStatus {
+    return static_cast<TaskStatus>(taca_texture_status(texture));
+}
+
+auto texture_target_new(std::size_t width, std::size_t height) -> Texture {
+    return taca_texture_target_new(width, height);
+}
+
+auto texture_update(
+    Texture texture, ByteSpan pixels, std::size_t x, std::size_t y,
+    std::size_t width, std::size_t height
+) -> void {
+    taca_texture_update(texture, to_taca(pixels), x, y, width, height);
+}
+
 auto title_update(std::string_view text) -> void {
     taca_title_update(to_taca(text));
 }
@@ -16,6 +16,38 @@ macro exportWasm*(def: untyped): untyped =
 type
   # Enums
 
+  # Alpha expects straight rather than premultiplied color, replace skips
+  # blending entirely, and custom uses the given components. Blend enums are
+  # pure to keep names like one, min, and max from clashing.
+  Blend* {.pure.} = enum
+    alpha
+    premultiplied
+    additive
+    multiply
+    replace
+    custom
+
+  BlendFactor* {.pure.} = enum
+    zero
+    one
+    src
+    oneMinusSrc
+    srcAlpha
+    oneMinusSrcAlpha
+    dst
+    oneMinusDst
+    dstAlpha
+    oneMinusDstAlpha
+    srcAlphaSaturated
+
+  # Min and max ignore factors.
+  BlendOperation* {.pure.} = enum
+    add
+    subtract
+    reverseSubtract
+    min
+    max
+
   BufferKind* = enum
     vertex
     index
@@ -84,6 +116,16 @@ type
     samplers: Span[Sampler]
     textures: Span[Texture]
 
+  BlendComponentExtern* = object
+    srcFactor: uint32
+    dstFactor: uint32
+    operation: uint32
+
+  BlendInfoExtern* = object
+    preset: uint32
+    color: BlendComponentExtern
+    alpha: BlendComponentExtern
+
   BuffersExtern* = object
     vertexBuffers: Span[Buffer]
     indexBuffer: Buffer
@@ -103,6 +145,7 @@ type
     vertex: PipelineShaderInfoExtern
     vertexAttributes: Span[AttributeInfo]
     vertexBuffers: Span[BufferInfo]
+    blend: BlendInfoExtern
 
   # Objects
 
@@ -117,6 +160,18 @@ type
     samplers: seq[Sampler]
     textures: seq[Texture]
 
+  BlendComponent* = object
+    srcFactor: BlendFactor
+    dstFactor: BlendFactor
+    operation: BlendOperation
+
+  # All defaults means straight alpha.
+  BlendInfo* = object
+    preset: Blend
+    # Only used for custom.
+    color: BlendComponent
+    alpha: BlendComponent
+
   BufferInfo* = object
     firstAttribute: uint
     step: Step
@@ -137,6 +192,7 @@ type
     vertex: PipelineShaderInfo
     vertexAttributes: seq[AttributeInfo]
     vertexBuffers: seq[BufferInfo]
+    blend: BlendInfo
 
   TextEvent* = object
     buffer: Buffer
@@ -165,8 +221,9 @@ proc tacaBuffersApply(buffers: BuffersExtern) {.importc: "taca_buffers_apply".}
 
 proc tacaKeyEvent*(): KeyEventExtern {.importc: "taca_key_event".}
 
+# Imports taca_pipeline_new2 for the current info layout.
 proc tacaPipelineNew(info: PipelineInfoExtern): Pipeline
-  {.importc: "taca_pipeline_new".}
+  {.importc: "taca_pipeline_new2".}
 
 proc tacaPrint(text: Span[char]) {.importc: "taca_print".}
 
@@ -202,6 +259,20 @@ proc toExtern(info: BindingsInfo): BindingsInfoExtern =
     textures: info.textures.toSpan,
   )
 
+proc toExtern(component: BlendComponent): BlendComponentExtern =
+  BlendComponentExtern(
+    srcFactor: uint32(component.srcFactor),
+    dstFactor: uint32(component.dstFactor),
+    operation: uint32(component.operation),
+  )
+
+proc toExtern(info: BlendInfo): BlendInfoExtern =
+  BlendInfoExtern(
+    preset: uint32(info.preset),
+    color: info.color.toExtern,
+    alpha: info.alpha.toExtern,
+  )
+
 proc toExtern(info: PipelineShaderInfo): PipelineShaderInfoExtern =
   PipelineShaderInfoExtern(entry: info.entry.toSpan, shader: info.shader)
 
@@ -212,6 +283,7 @@ proc toExtern(info: PipelineInfo): PipelineInfoExtern =
     vertex: info.vertex.toExtern,
     vertexAttributes: info.vertexAttributes.toSpan,
     vertexBuffers: info.vertexBuffers.toSpan,
+    blend: info.blend.toExtern,
   )
 
 # Main api
@@ -8,6 +8,53 @@ pub const BindingsInfo = struct {
     textures: []const *Texture = &[_]*Texture{},
 };
 
+// Alpha expects straight rather than premultiplied color, replace skips
+// blending entirely, and custom uses the given components.
+pub const Blend = enum(c_int) {
+    alpha,
+    premultiplied,
+    additive,
+    multiply,
+    replace,
+    custom,
+};
+
+pub const BlendComponent = extern struct {
+    src_factor: BlendFactor = .one,
+    dst_factor: BlendFactor = .zero,
+    operation: BlendOperation = .add,
+};
+
+pub const BlendFactor = enum(c_int) {
+    zero,
+    one,
+    src,
+    one_minus_src,
+    src_alpha,
+    one_minus_src_alpha,
+    dst,
+    one_minus_dst,
+    dst_alpha,
+    one_minus_dst_alpha,
+    src_alpha_saturated,
+};
+
+pub const BlendInfo = extern struct {
+    preset: Blend = .alpha,
+    // Only used for custom.
+    color: BlendComponent = .{},
+    alpha: BlendComponent = .{},
+};
+
+// Min and max ignore factors.
+pub const BlendOperation = enum(c_int) {
+    add,
+    subtract,
+    reverse_subtract,
+    min,
+    max,
+};
+
 pub const Buffer = extern struct {};
 
 pub const BufferInfo = extern struct {
@@ -63,6 +110,7 @@ pub const PipelineInfo = struct {
     vertex: PipelineShaderInfo = .{},
     vertex_attributes: []const VertexAttribute = &[_]VertexAttribute{},
     vertex_buffers: []const VertexBufferLayout = &[_]VertexBufferLayout{},
+    blend: BlendInfo = .{},
 };
 
 pub const RenderingContext = struct {
@@ -105,7 +153,7 @@ pub const RenderingContext = struct {
     }
 
     pub fn newPipeline(info: PipelineInfo) *Pipeline {
-        return taca_pipeline_new(ExternPipelineInfo.from(info));
+        return taca_pipeline_new2(ExternPipelineInfo.from(info));
     }
 
     pub fn newShader(bytes: []const u8) *Shader {
@@ -250,6 +298,7 @@ const ExternPipelineInfo = extern struct {
     vertex: ExternPipelineShaderInfo,
     vertex_attributes: Span(VertexAttribute),
     vertex_buffers: Span(VertexBufferLayout),
+    blend: BlendInfo,
 
     pub fn from(info: PipelineInfo) ExternPipelineInfo {
         return .{
@@ -258,6 +307,7 @@ const ExternPipelineInfo = extern struct {
             .vertex = ExternPipelineShaderInfo.from(info.vertex),
             .vertex_attributes = Span(VertexAttribute).from(info.vertex_attributes),
             .vertex_buffers = Span(VertexBufferLayout).from(info.vertex_buffers),
+            .blend = info.blend,
         };
     }
 };
@@ -319,7 +369,8 @@ extern fn taca_buffer_new(
     info: *const BufferSlice,
 ) callconv(.C) *Buffer;
 
-extern fn taca_pipeline_new(
+// Takes the layout with blend info, unlike the older taca_pipeline_new.
+extern fn taca_pipeline_new2(
     info: ExternPipelineInfo,
 ) callconv(.C) *Pipeline;
 
@@ -2,11 +2,10 @@
 
 use std::{
     collections::HashMap,
-    fs::File,
-    io::{Cursor, Read},
+    mem::take,
     sync::{
         mpsc::{channel, Sender},
-        Arc, Mutex,
+        Arc, Mutex, PoisonError,
     },
     thread,
     time::Duration,
@@ -14,37 +13,59 @@ use std::{
 
 use kira::{
     manager::{AudioManager, AudioManagerSettings},
-    sound::PlaybackRate,
-    StartTime, Volume,
+    sound::{static_sound::StaticSoundSettings, PlaybackState},
+    track::TrackHandle,
+    OutputDestination, StartTime,
 };
+use rand::{rngs::StdRng, SeedableRng};
 use wasmer::{
     imports, Extern, Function, FunctionEnv, FunctionEnvMut, Instance, Memory, MemoryView, Module,
-    Store, Value, ValueType, WasmPtr, WasmRef,
+    RuntimeError, Store, Value, ValueType, WasmPtr, WasmRef,
 };
 use winit::event_loop::EventLoop;
-use zip::ZipArchive;
 
 use crate::{
+    bundle::{is_bonus_export, Bundle, BundlePart},
     display::{Display, EventKind, Graphics, MaybeGraphics, UserEvent, WindowState},
+    error::{ErrorCode, HostError, HostResult},
     gpu::{
         bindings_apply, bindings_new, bound_ensure, buffer_update, buffered_ensure, buffers_apply,
-        create_buffer, create_pipeline, frame_commit, image_decode, image_to_texture, pass_ensure,
-        pipeline_apply, pipelined_ensure, shader_create, sound_decode, Bindings, BindingsInfo,
-        BufferSlice, ExternBindingsInfo, ExternMeshBuffers, ExternPipelineInfo, GpuBuffer,
-        MeshBuffers, Pipeline, PipelineInfo, PipelineShaderInfo, RenderFrame, Shader, Span,
-        Texture, TextureInfoExtern,
+        create_buffer, create_pipeline, frame_commit, image_decode, image_to_texture, pass_begin,
+        pass_ensure, pass_size, pipeline_apply, pipelined_ensure, shader_create, sound_decode,
+        texture_new, texture_target_new, texture_update, Bindings, BindingsInfo, BufferSlice,
+        ExternBindingsInfo, ExternMeshBuffers, ExternPipelineBaseInfo, ExternPipelineInfo,
+        GpuBuffer, MeshBuffers, PassInfoExtern, Pipeline, PipelineInfo, PipelineShaderInfo,
+        RenderFrame, Shader, Span, Texture, TextureInfoExtern, TextureNewInfoExtern,
     },
     key::{KeyEvent, TextEvent},
-    sound::{Sound, SoundPlayInfoExtern},
-    text::{to_text_align_x, to_text_align_y, TextEngine},
-    wasi,
+    pool::Pool,
+    sound::{
+        loop_region_from_extern, panning_from_extern, rate_from_extern, track_builder_from_extern,
+        tween_from_extern, volume_from_extern, Sound, SoundEffectInfoExtern, SoundInfoExtern,
+        SoundPlay, SoundPlayBaseInfoExtern, SoundPlayInfoExtern, SoundTrackInfoExtern,
+    },
+    storage::Storage,
+    stream::{SoundStream, SoundStreamInfoExtern},
+    task::{TaskEvent, TaskKind, TaskStatus},
+    text::{to_text_align_x, to_text_align_y, TextAlignX, TextAlignY, TextEngine},
+    wasi::{self, BundleFiles, Clock, ExitCode},
 };
 
+/// Decoding gains little from more threads than this.
+const WORKERS_MAX: usize = 4;
+
 pub struct App {
+    pub halt: Option<Halt>,
     pub store: Store,
     pub system: Arc<Mutex<System>>,
 }
 
+/// Why parts stopped running.
+pub enum Halt {
+    Exit(u32),
+    Failure(String),
+}
+
 // TODO Arc Mutex the app instead?
 pub struct AppPtr(pub *mut App);
 
@@ -53,79 +74,142 @@ unsafe impl Send for AppPtr {}
 pub struct Part {
     pub env: FunctionEnv<PartData>,
     pub instance: Instance,
+    pub name: String,
     pub update: Option<Function>,
 }
 
 impl Part {
-    fn init(&mut self, store: &mut Store) {
+    fn init(&mut self, store: &mut Store) -> Result<(), RuntimeError> {
         // Some wasi builds make _initialize even without main/_start.
         // If _start exists, it's supposed to do any initialize on its own, I think.
         if let Ok(initialize) = self.instance.exports.get_function("_initialize") {
-            initialize.call(store, &[]).unwrap();
+            initialize.call(store, &[])?;
         } else if let Ok(main) = self.instance.exports.get_function("_start") {
-            main.call(store, &[]).unwrap();
+            main.call(store, &[])?;
         }
         // Part of why to move away from main/_start so we know any _initialize
         // is separate from our own start.
         if let Ok(start) = self.instance.exports.get_function("start") {
-            start.call(store, &[]).unwrap();
+            start.call(store, &[])?;
         }
+        Ok(())
     }
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct AppOptions {
+    /// Command line args for wasi, starting with the app path.
+    pub args: Vec<String>,
+    /// Seeds a deterministic rng in place of system entropy.
+    pub seed: Option<u64>,
+    /// Advances the monotonic clock a fixed step per frame instead of by real time.
+    pub stepped_clock: bool,
+}
+
 pub struct PartData {
     pub memory: Option<Memory>,
     pub system: Arc<Mutex<System>>,
 }
 
 impl App {
-    fn init(wasms: Vec<Vec<u8>>, display: Display) -> App {
+    fn init(mut bundle: Bundle, display: Display, options: AppOptions, storage: Storage) -> App {
         let mut parts = vec![];
         // Prep building parts.
         let mut store = Store::default();
-        let system = Arc::new(Mutex::new(System::new(display)));
+        let system = Arc::new(Mutex::new(System::new(
+            display,
+            options,
+            BundleFiles::new(take(&mut bundle.files)),
+            storage,
+        )));
         let mut make_part =
-            |wasm: &Vec<u8>, parts: &mut Vec<Part>, bonus_exports: &mut HashMap<String, Extern>| {
-                let module = Module::new(&store, wasm).unwrap();
+            |wasm: &BundlePart,
+             parts: &mut Vec<Part>,
+             bonus_exports: &mut HashMap<String, Extern>| {
+                let module = Module::new(&store, &wasm.wasm).unwrap();
                 let part_data = PartData {
                     memory: None,
                     system: system.clone(),
                 };
                 let env = FunctionEnv::new(&mut store, part_data);
+                // Keep bundle::ENV_IMPORTS and WASI_IMPORTS in sync with these.
                 let mut import_object = imports! {
                     "env" => {
                         "taca_bindings_apply" => Function::new_typed_with_env(&mut store, &env, taca_bindings_apply),
                         "taca_bindings_new" => Function::new_typed_with_env(&mut store, &env, taca_bindings_new),
+                        "taca_buffer_drop" => Function::new_typed_with_env(&mut store, &env, taca_buffer_drop),
                         "taca_buffer_new" => Function::new_typed_with_env(&mut store, &env, taca_buffer_new),
                         "taca_buffer_read" => Function::new_typed_with_env(&mut store, &env, taca_buffer_read),
                         "taca_buffer_update" => Function::new_typed_with_env(&mut store, &env, taca_buffer_update),
                         "taca_buffers_apply" => Function::new_typed_with_env(&mut store, &env, taca_buffers_apply),
                         "taca_clip" => Function::new_typed_with_env(&mut store, &env, taca_clip),
                         "taca_draw" => Function::new_typed_with_env(&mut store, &env, taca_draw),
+                        "taca_error_last" => Function::new_typed_with_env(&mut store, &env, taca_error_last),
                         "taca_image_decode" => Function::new_typed_with_env(&mut store, &env, taca_image_decode),
                         "taca_key_event" => Function::new_typed_with_env(&mut store, &env, taca_key_event),
+                        "taca_pass_begin" => Function::new_typed_with_env(&mut store, &env, taca_pass_begin),
                         "taca_pipeline_apply" => Function::new_typed_with_env(&mut store, &env, taca_pipeline_apply),
+                        "taca_pipeline_drop" => Function::new_typed_with_env(&mut store, &env, taca_pipeline_drop),
                         "taca_pipeline_new" => Function::new_typed_with_env(&mut store, &env, taca_pipeline_new),
+                        "taca_pipeline_new2" => Function::new_typed_with_env(&mut store, &env, taca_pipeline_new2),
                         "taca_print" => Function::new_typed_with_env(&mut store, &env, taca_print),
+                        "taca_shader_drop" => Function::new_typed_with_env(&mut store, &env, taca_shader_drop),
                         "taca_shader_new" => Function::new_typed_with_env(&mut store, &env, taca_shader_new),
                         "taca_sound_decode" => Function::new_typed_with_env(&mut store, &env, taca_sound_decode),
+                        "taca_sound_decode_mode" => Function::new_typed_with_env(&mut store, &env, taca_sound_decode_mode),
+                        "taca_sound_drop" => Function::new_typed_with_env(&mut store, &env, taca_sound_drop),
+                        "taca_sound_info" => Function::new_typed_with_env(&mut store, &env, taca_sound_info),
+                        "taca_sound_panning_update" => Function::new_typed_with_env(&mut store, &env, taca_sound_panning_update),
+                        "taca_sound_pause" => Function::new_typed_with_env(&mut store, &env, taca_sound_pause),
                         "taca_sound_play" => Function::new_typed_with_env(&mut store, &env, taca_sound_play),
+                        "taca_sound_play2" => Function::new_typed_with_env(&mut store, &env, taca_sound_play2),
+                        "taca_sound_rate_update" => Function::new_typed_with_env(&mut store, &env, taca_sound_rate_update),
+                        "taca_sound_resume" => Function::new_typed_with_env(&mut store, &env, taca_sound_resume),
+                        "taca_sound_status" => Function::new_typed_with_env(&mut store, &env, taca_sound_status),
+                        "taca_sound_status_message" => Function::new_typed_with_env(&mut store, &env, taca_sound_status_message),
+                        "taca_sound_stop" => Function::new_typed_with_env(&mut store, &env, taca_sound_stop),
+                        "taca_sound_stream_drop" => Function::new_typed_with_env(&mut store, &env, taca_sound_stream_drop),
+                        "taca_sound_stream_new" => Function::new_typed_with_env(&mut store, &env, taca_sound_stream_new),
+                        "taca_sound_stream_space" => Function::new_typed_with_env(&mut store, &env, taca_sound_stream_space),
+                        "taca_sound_stream_write" => Function::new_typed_with_env(&mut store, &env, taca_sound_stream_write),
+                        "taca_sound_track_drop" => Function::new_typed_with_env(&mut store, &env, taca_sound_track_drop),
+                        "taca_sound_track_new" => Function::new_typed_with_env(&mut store, &env, taca_sound_track_new),
+                        "taca_sound_track_volume_update" => Function::new_typed_with_env(&mut store, &env, taca_sound_track_volume_update),
+                        "taca_sound_volume_update" => Function::new_typed_with_env(&mut store, &env, taca_sound_volume_update),
+                        "taca_storage_read" => Function::new_typed_with_env(&mut store, &env, taca_storage_read),
+                        "taca_storage_write" => Function::new_typed_with_env(&mut store, &env, taca_storage_write),
+                        "taca_task_event" => Function::new_typed_with_env(&mut store, &env, taca_task_event),
                         "taca_text_align" => Function::new_typed_with_env(&mut store, &env, taca_text_align),
                         "taca_text_draw" => Function::new_typed_with_env(&mut store, &env, taca_text_draw),
                         "taca_text_event" => Function::new_typed_with_env(&mut store, &env, taca_text_event),
+                        "taca_texture_drop" => Function::new_typed_with_env(&mut store, &env, taca_texture_drop),
                         "taca_texture_info" => Function::new_typed_with_env(&mut store, &env, taca_texture_info),
+                        "taca_texture_new" => Function::new_typed_with_env(&mut store, &env, taca_texture_new),
+                        "taca_texture_status" => Function::new_typed_with_env(&mut store, &env, taca_texture_status),
+                        "taca_texture_status_message" => Function::new_typed_with_env(&mut store, &env, taca_texture_status_message),
+                        "taca_texture_target_new" => Function::new_typed_with_env(&mut store, &env, taca_texture_target_new),
+                        "taca_texture_update" => Function::new_typed_with_env(&mut store, &env, taca_texture_update),
                         "taca_title_update" => Function::new_typed_with_env(&mut store, &env, taca_title_update),
                         "taca_window_state" => Function::new_typed_with_env(&mut store, &env, taca_window_state),
                     },
                     "wasi_snapshot_preview1" => {
                         "args_get" => Function::new_typed_with_env(&mut store, &env, wasi::args_get),
                         "args_sizes_get" => Function::new_typed_with_env(&mut store, &env, wasi::args_sizes_get),
+                        "clock_res_get" => Function::new_typed_with_env(&mut store, &env, wasi::clock_res_get),
+                        "clock_time_get" => Function::new_typed_with_env(&mut store, &env, wasi::clock_time_get),
                         "fd_close" => Function::new_typed_with_env(&mut store, &env, wasi::fd_close),
                         "fd_fdstat_get" => Function::new_typed_with_env(&mut store, &env, wasi::fd_fdstat_get),
+                        "fd_filestat_get" => Function::new_typed_with_env(&mut store, &env, wasi::fd_filestat_get),
+                        "fd_prestat_dir_name" => Function::new_typed_with_env(&mut store, &env, wasi::fd_prestat_dir_name),
+                        "fd_prestat_get" => Function::new_typed_with_env(&mut store, &env, wasi::fd_prestat_get),
+                        "fd_read" => Function::new_typed_with_env(&mut store, &env, wasi::fd_read),
+                        "fd_readdir" => Function::new_typed_with_env(&mut store, &env, wasi::fd_readdir),
                         "fd_seek" => Function::new_typed_with_env(&mut store, &env, wasi::fd_seek),
                         "fd_write" => Function::new_typed_with_env(&mut store, &env, wasi::fd_write),
+                        "path_filestat_get" => Function::new_typed_with_env(&mut store, &env, wasi::path_filestat_get),
+                        "path_open" => Function::new_typed_with_env(&mut store, &env, wasi::path_open),
                         "proc_exit" => Function::new_typed(&mut store, wasi::proc_exit),
-                        "random_get" => Function::new_typed(&mut store, wasi::random_get),
+                        "random_get" => Function::new_typed_with_env(&mut store, &env, wasi::random_get),
                     },
                 };
                 for (bonus_key, bonus_export) in bonus_exports.iter() {
@@ -139,12 +223,13 @@ impl App {
                 let part = Part {
                     env,
                     instance,
+                    name: wasm.name.clone(),
                     update,
                 };
                 parts.push(part);
             };
         // Separate last as app from earlier extensions.
-        let (app_wasm, ext_wasms) = wasms.split_last().unwrap();
+        let (app_wasm, ext_wasms) = bundle.parts.split_last().unwrap();
         let mut bonus_exports = HashMap::new();
         for wasm in ext_wasms {
             make_part(wasm, &mut parts, &mut bonus_exports);
@@ -152,13 +237,9 @@ impl App {
         // Build bonus exports to import into app.
         for part in &parts {
             for (key, export) in part.instance.exports.iter() {
-                match key.as_str() {
-                    "init" | "initialize" | "start" | "update" => {}
-                    _ if key.starts_with('_') || key.starts_with("taca_") || key.contains('.') => {}
-                    _ => {
-                        bonus_exports.insert(key.clone(), export.clone());
-                    }
-                };
+                if is_bonus_export(key) {
+                    bonus_exports.insert(key.clone(), export.clone());
+                }
             }
         }
         // Make and finish app.
@@ -167,38 +248,62 @@ impl App {
             let mut system = system.lock().unwrap();
             system.parts = parts;
         }
-        App { store, system }
+        App {
+            halt: None,
+            store,
+            system,
+        }
+    }
+
+    /// Stops running parts, reporting any failure other than exit.
+    fn fail(&mut self, part_name: &str, err: RuntimeError) {
+        if let Some(ExitCode(code)) = err.downcast_ref::<ExitCode>() {
+            self.halt = Some(Halt::Exit(*code));
+            return;
+        }
+        let mut message = format!("{part_name}: {}", err.message());
+        for frame in err.trace() {
+            let function = match frame.function_name() {
+                Some(name) => name.to_string(),
+                None => format!("<func {}>", frame.func_index()),
+            };
+            let module = frame.module_name();
+            let offset = frame.module_offset();
+            message.push_str(&format!("\n    at {function} ({module}:{offset:#x})"));
+        }
+        eprintln!("{message}");
+        self.halt = Some(Halt::Failure(message));
     }
 
     pub fn handle(&mut self, event: UserEvent) {
         match event {
             UserEvent::Graphics(_) => {} // handled in display
             UserEvent::ImageDecoded { handle, image } => {
-                match image {
+                let result = match image {
                     Ok(image) => {
                         let mut system = self.system.lock().unwrap();
                         image_to_texture(&mut system, handle, image);
+                        Ok(())
                     }
-                    Err(err) => {
-                        // TODO Report errors to app?
-                        dbg!(err);
-                    }
-                }
-                self.task_finish();
+                    Err(err) => Err(err.to_string()),
+                };
+                self.task_finish(TaskKind::Image, handle, result);
             }
             UserEvent::SoundDecoded { handle, sound } => {
-                match *sound {
-                    Ok(sound) => {
+                let result = match *sound {
+                    Ok((sound, info)) => {
                         let mut system = self.system.lock().unwrap();
-                        // dbg!(sound.duration());
-                        system.sounds[handle - 1].data = Some(sound);
-                    }
-                    Err(err) => {
-                        // TODO Report errors to app?
-                        dbg!(err);
+                        // dbg!(info);
+                        // Skip sounds dropped while decoding.
+                        if let Ok(it) = system.sounds.get_mut(handle) {
+                            it.data = Some(sound);
+                            it.info = info;
+                        }
+                        Ok(())
                     }
-                }
-                self.task_finish();
+                    Err(err) => Err(err.to_string()),
+                };
+                self.task_finish(TaskKind::Sound, handle, result);
             }
         }
     }
@@ -206,45 +311,25 @@ impl App {
     pub fn listen(&mut self) {
         self.parts_update(EventKind::Frame);
         let mut system = self.system.lock().unwrap();
+        if let Some(Halt::Failure(message)) = &self.halt {
+            // Show just the top line, since the trace is in the console.
+            let message = message.lines().next().unwrap_or_default();
+            failure_draw(&mut system, message);
+        }
         frame_commit(&mut system);
+        system.clock.frame_advance();
     }
 
-    pub fn load(path: &str, display: Display) -> App {
-        let mut buf = Vec::new();
-        File::open(path)
-            .expect("Bad open")
-            .read_to_end(&mut buf)
-            .expect("Bad read");
-        let bufs = if buf[0] == 0x50 {
-            let mut bufs: Vec<Vec<u8>> = vec![];
-            let mut zip = ZipArchive::new(Cursor::new(buf)).unwrap();
-            // Read extensions.
-            for i in 0..zip.len() {
-                let mut file = zip.by_index(i).unwrap();
-                let name = file.name();
-                if let Some(ext_name) = name.strip_prefix("ext/") {
-                    if !ext_name.contains('/') && ext_name.ends_with(".wasm") {
-                        let mut buf = Vec::new();
-                        file.read_to_end(&mut buf).unwrap();
-                        bufs.push(buf);
-                    }
-                }
-            }
-            // Read app.
-            {
-                let mut file = zip.by_name("app.wasm").unwrap();
-                let mut buf = Vec::new();
-                file.read_to_end(&mut buf).unwrap();
-                bufs.push(buf);
-            }
-            bufs
-        } else {
-            vec![buf]
-        };
-        App::init(bufs, display)
+    pub fn load(path: &str, options: AppOptions, display: Display) -> App {
+        let bundle = Bundle::read(path).unwrap_or_else(|err| panic!("{err}"));
+        let storage = Storage::new(&bundle.storage_name());
+        App::init(bundle, display, options, storage)
     }
 
     pub fn parts_update(&mut self, kind: EventKind) {
+        if self.halt.is_some() {
+            return;
+        }
         let parts: *mut Vec<Part> = {
             let system = self.system.lock().unwrap();
             &system.parts as *const _ as *mut _
@@ -252,34 +337,21 @@ impl App {
         unsafe {
             for part in parts.as_mut().unwrap() {
                 if let Some(update) = &part.update {
-                    update
-                        .call(&mut self.store, &[Value::I32(kind as i32)])
-                        .unwrap();
+                    let result = update.call(&mut self.store, &[Value::I32(kind as i32)]);
+                    if let Err(err) = result {
+                        self.fail(&part.name, err);
+                        return;
+                    }
                 };
             }
         }
     }
 
     pub fn run(&mut self, event_loop: EventLoop<UserEvent>, ptr: *mut App) {
+        let event_loop_proxy = event_loop.create_proxy();
+        self.worker_start(move |event| event_loop_proxy.send_event(event).unwrap());
         let display: *mut Display = {
             let mut system = self.system.lock().unwrap();
-            // Set up worker thread, and detach. TODO Do we ever need it?
-            let (sender, receiver) = channel();
-            system.worker = Some(sender);
-            let event_loop_proxy = event_loop.create_proxy();
-            // TODO Arc mutex the receiver for multiple worker threads?
-            thread::spawn(move || {
-                for message in receiver {
-                    match message {
-                        WorkItem::ImageDecode { handle, bytes } => {
-                            image_decode(handle, bytes, &event_loop_proxy);
-                        }
-                        WorkItem::SoundDecode { handle, bytes } => {
-                            sound_decode(handle, bytes, &event_loop_proxy);
-                        }
-                    }
-                }
-            });
             // Run event loop.
             system.display.app = AppPtr(ptr);
             &system.display as *const _ as *mut _
@@ -295,17 +367,78 @@ impl App {
         };
         unsafe {
             for part in parts.as_mut().unwrap() {
-                part.init(&mut self.store);
+                if let Err(err) = part.init(&mut self.store) {
+                    self.fail(&part.name, err);
+                    return;
+                }
             }
         }
     }
 
-    fn task_finish(&mut self) {
-        let done = {
+    /// Sends results of background work to the given function.
+    pub fn worker_start(&mut self, send: impl Fn(UserEvent) + Clone + Send + 'static) {
+        let mut system = self.system.lock().unwrap();
+        // Set up worker threads sharing one queue, and detach.
+        let (sender, receiver) = channel();
+        system.worker = Some(sender);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let count = thread::available_parallelism().map_or(1, |count| count.get());
+        let count = count.min(WORKERS_MAX);
+        for _ in 0..count {
+            let receiver = receiver.clone();
+            let send = send.clone();
+            thread::spawn(move || loop {
+                // Lock only while waiting so others can decode meanwhile.
+                let Ok(message) = receiver
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv()
+                else {
+                    break;
+                };
+                let event = match message {
+                    WorkItem::ImageDecode { handle, bytes } => image_decode(handle, bytes),
+                    WorkItem::SoundDecode {
+                        handle,
+                        bytes,
+                        mode,
+                    } => sound_decode(handle, bytes, mode),
+                };
+                send(event);
+            });
+        }
+    }
+
+    /// Tells the app about each finished task, keeping any failure message for
+    /// status queries, and then about all tasks being done.
+    fn task_finish(&mut self, kind: TaskKind, handle: u32, result: Result<(), String>) {
+        if let Err(message) = &result {
+            eprintln!("{kind:?} task {handle} failed: {message}");
+        }
+        let (event, done) = {
             let mut system = self.system.lock().unwrap();
             system.tasks_active -= 1;
-            system.tasks_active == 0
+            let failure = match kind {
+                TaskKind::Image => system.textures.get_mut(handle).map(|it| &mut it.failure),
+                TaskKind::Sound => system.sounds.get_mut(handle).map(|it| &mut it.failure),
+            };
+            let event = match (failure, result) {
+                // Skip items dropped while decoding.
+                (Err(_), _) => None,
+                (Ok(_), Ok(())) => Some((EventKind::TaskDone, TaskStatus::Ready)),
+                (Ok(failure), Err(message)) => {
+                    *failure = Some(message);
+                    Some((EventKind::TaskFailed, TaskStatus::Failed))
+                }
+            };
+            if let Some((_, status)) = event {
+                system.task_event = TaskEvent::new(kind, handle, status);
+            }
+            (event, system.tasks_active == 0)
         };
+        if let Some((event_kind, _)) = event {
+            self.parts_update(event_kind);
+        }
         if done {
             self.parts_update(EventKind::TasksDone);
         }
@@ -345,27 +478,40 @@ pub struct CpuBuffer {
 }
 
 pub struct System {
+    /// Command line args for wasi, starting with the app path.
+    pub args: Vec<String>,
     pub audio_manager: Option<AudioManager>,
     pub bindings: Vec<Bindings>,
     pub bindings_updated: Vec<usize>, // TODO Track by buffer per queue instead?
-    pub buffers: Vec<Buffer>,
+    pub buffers: Pool<Buffer>,
+    pub clock: Clock,
     pub display: Display,
+    pub error_last: ErrorCode,
+    /// Last error printed, to avoid flooding output with repeats every frame.
+    error_message: String,
+    pub files: BundleFiles,
     pub frame: Option<RenderFrame>,
     pub key_event: KeyEvent,
     pub parts: Vec<Part>,
-    pub pipelines: Vec<Pipeline>,
+    pub pipelines: Pool<Pipeline>,
+    pub rng: StdRng,
     pub samplers: Vec<wgpu::Sampler>,
-    pub shaders: Vec<Shader>,
-    pub sounds: Vec<Sound>,
+    pub shaders: Pool<Shader>,
+    pub sound_plays: Pool<SoundPlay>,
+    pub sound_streams: Pool<SoundStream>,
+    pub sound_tracks: Pool<TrackHandle>,
+    pub sounds: Pool<Sound>,
+    pub storage: Storage,
+    pub task_event: TaskEvent,
     pub tasks_active: usize,
     pub text: Option<Arc<Mutex<TextEngine>>>,
-    pub text_buffer: usize,
-    pub textures: Vec<Texture>,
+    pub text_buffer: u32,
+    pub textures: Pool<Texture>,
     pub worker: Option<Sender<WorkItem>>,
 }
 
 impl System {
-    fn new(display: Display) -> System {
+    fn new(display: Display, options: AppOptions, files: BundleFiles, storage: Storage) -> System {
         let audio_manager = AudioManager::<kira::manager::backend::DefaultBackend>::new(
             AudioManagerSettings::default(),
         );
@@ -376,34 +522,62 @@ impl System {
                 None
             }
         };
+        let rng = match options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
         System {
+            args: options.args,
             audio_manager,
             bindings: vec![],
             bindings_updated: vec![],
-            buffers: vec![],
+            buffers: Pool::new("buffer"),
+            clock: Clock::new(options.stepped_clock),
             display,
+            error_last: Default::default(),
+            error_message: String::new(),
+            files,
             key_event: Default::default(),
             frame: None,
             parts: vec![],
-            pipelines: vec![],
+            pipelines: Pool::new("pipeline"),
+            rng,
             samplers: vec![],
-            shaders: vec![],
-            sounds: vec![],
+            shaders: Pool::new("shader"),
+            sound_plays: Pool::new("sound play"),
+            sound_streams: Pool::new("sound stream"),
+            sound_tracks: Pool::new("sound track"),
+            sounds: Pool::new("sound"),
+            storage,
+            task_event: Default::default(),
             tasks_active: 0,
             text: None,
             text_buffer: 0,
-            textures: vec![],
+            textures: Pool::new("texture"),
             worker: None,
         }
     }
 
+    /// Records the error for taca_error_last.
+    pub fn error_set(&mut self, name: &str, err: HostError) {
+        let message = format!("{name}: {err}");
+        if message != self.error_message {
+            eprintln!("{message}");
+            self.error_message = message;
+        }
+        self.error_last = err.code();
+    }
+
     pub fn update_text_buffer(&mut self, text: &str) {
-        if self.text_buffer == 0 {
-            self.buffers
-                .push(Buffer::CpuBuffer(CpuBuffer { data: vec![] }));
-            self.text_buffer = self.buffers.len();
+        // Also replace the buffer if the app dropped it.
+        if self.buffers.get(self.text_buffer).is_err() {
+            let buffer = Buffer::CpuBuffer(CpuBuffer { data: vec![] });
+            let Ok(handle) = self.buffers.insert(buffer) else {
+                return;
+            };
+            self.text_buffer = handle;
         }
-        let Buffer::CpuBuffer(buffer) = &mut self.buffers[self.text_buffer - 1] else {
+        let Ok(Buffer::CpuBuffer(buffer)) = self.buffers.get_mut(self.text_buffer) else {
             panic!()
         };
         buffer.data.clear();
@@ -413,162 +587,319 @@ impl System {
 
 #[derive(Debug)]
 pub enum WorkItem {
-    ImageDecode { handle: usize, bytes: Vec<u8> },
-    SoundDecode { handle: usize, bytes: Vec<u8> },
+    ImageDecode {
+        handle: u32,
+        bytes: Vec<u8>,
+    },
+    SoundDecode {
+        handle: u32,
+        bytes: Vec<u8>,
+        mode: u32,
+    },
+}
+
+fn failure_draw(system: &mut System, message: &str) {
+    pass_ensure(system);
+    let Some(text_engine) = system.text.clone() else {
+        return;
+    };
+    let mut text_engine = text_engine.lock().unwrap();
+    text_engine.align_x = TextAlignX::Left;
+    text_engine.align_y = TextAlignY::Top;
+    text_engine.draw(system, message, 10.0, 10.0);
+}
+
+/// Runs a host call on the locked system, where errors go to
+/// taca_error_last and the app gets a default result.
+fn host_call<R: Default>(
+    env: &mut FunctionEnvMut<PartData>,
+    name: &str,
+    call: impl FnOnce(&mut System, &MemoryView) -> HostResult<R>,
+) -> R {
+    let (part, store) = env.data_and_store_mut();
+    let mut system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    match call(&mut system, &view) {
+        Ok(result) => result,
+        Err(err) => {
+            system.error_set(name, err);
+            R::default()
+        }
+    }
+}
+
+fn pipeline_new(
+    system: &mut System,
+    view: &MemoryView,
+    info: ExternPipelineInfo,
+) -> HostResult<u32> {
+    // dbg!(info);
+    // println!("{info:?}");
+    let vertex_attributes = read_span(view, info.vertex_attributes)?;
+    let vertex_buffers = read_span(view, info.vertex_buffers)?;
+    let info = PipelineInfo {
+        depth_test: info.depth_test,
+        fragment: PipelineShaderInfo {
+            entry_point: read_string(view, info.fragment.entry_point)?,
+            shader: info.fragment.shader,
+        },
+        vertex: PipelineShaderInfo {
+            entry_point: read_string(view, info.vertex.entry_point)?,
+            shader: info.vertex.shader,
+        },
+        vertex_attributes,
+        vertex_buffers,
+        blend: info.blend,
+    };
+    // dbg!(&info);
+    create_pipeline(system, info)
 }
 
-fn read_span<T>(view: &MemoryView, span: Span) -> Vec<T>
+fn sound_decode_start(
+    system: &mut System,
+    view: &MemoryView,
+    bytes: u32,
+    mode: u32,
+) -> HostResult<u32> {
+    let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+    let bytes = read_span(view, bytes)?;
+    let handle = system.sounds.insert(Sound {
+        data: None,
+        failure: None,
+        info: Default::default(),
+    })?;
+    let sent = work_send(
+        system,
+        WorkItem::SoundDecode {
+            handle,
+            bytes,
+            mode,
+        },
+    );
+    if sent.is_err() {
+        system.sounds.remove(handle)?;
+    }
+    sent.map(|_| handle)
+}
+
+fn sound_play(system: &mut System, info: SoundPlayInfoExtern) -> HostResult<u32> {
+    // dbg!(info);
+    let source = system
+        .sounds
+        .get(info.sound)?
+        .data
+        .as_ref()
+        .ok_or(HostError::NotReady("sound"))?;
+    let mut settings = StaticSoundSettings::default();
+    // Track 0 is also what apps get when audio is unavailable.
+    if info.track != 0 {
+        settings.output_destination = system.sound_tracks.get(info.track)?.into();
+    }
+    let Some(audio_manager) = &mut system.audio_manager else {
+        // eprintln!("no audio manager");
+        return Ok(0);
+    };
+    if info.delay > 0.0 {
+        settings.start_time = StartTime::Delayed(Duration::from_secs_f32(info.delay));
+    }
+    if let Some(rate) = rate_from_extern(info.rate, info.rate_kind) {
+        settings.playback_rate = rate.into();
+    }
+    if let Some(volume) = volume_from_extern(info.volume, info.volume_kind) {
+        settings.volume = volume.into();
+    }
+    if info.looping {
+        settings.loop_region = Some(loop_region_from_extern(info.loop_start, info.loop_end));
+    }
+    settings.panning = panning_from_extern(info.panning).into();
+    let play = match source.play(audio_manager, settings) {
+        Ok(play) => play,
+        Err(message) => {
+            // Keep the failure for taca_sound_status_message.
+            eprintln!("sound {} play failed: {message}", info.sound);
+            system.sounds.get_mut(info.sound)?.failure = Some(message);
+            return Ok(0);
+        }
+    };
+    // Finished plays can't restart, so free their handles.
+    system
+        .sound_plays
+        .retain(|play| play.state() != PlaybackState::Stopped);
+    system.sound_plays.insert(play)
+}
+
+/// Copies as much of any failure message as fits, returning its full size.
+fn status_message_write(
+    view: &MemoryView,
+    failure: &Option<String>,
+    bytes: u32,
+) -> HostResult<u32> {
+    let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+    let Some(message) = failure else {
+        return Ok(0);
+    };
+    let len = (bytes.len as usize).min(message.len());
+    view.write(bytes.ptr as u64, &message.as_bytes()[..len])?;
+    Ok(message.len() as u32)
+}
+
+/// Plays that already finished and got freed are ignored rather than reported,
+/// since apps can't know exactly when a play ends, and play 0 means no audio.
+fn sound_play_update(
+    env: &mut FunctionEnvMut<PartData>,
+    name: &str,
+    play: u32,
+    update: impl FnOnce(&mut SoundPlay),
+) {
+    host_call(env, name, |system, _| {
+        if let Ok(play) = system.sound_plays.get_mut(play) {
+            update(play);
+        }
+        Ok(())
+    })
+}
+
+fn read_span<T>(view: &MemoryView, span: Span) -> HostResult<Vec<T>>
 where
     T: Copy + ValueType,
 {
-    match span.len {
+    Ok(match span.len {
         0 => vec![],
         _ => WasmPtr::<T>::new(span.ptr)
-            .slice(view, span.len)
-            .unwrap()
-            .read_to_vec()
-            .unwrap(),
-    }
+            .slice(view, span.len)?
+            .read_to_vec()?,
+    })
 }
 
-fn read_string(view: &MemoryView, span: Span) -> String {
-    match span.len {
+fn read_string(view: &MemoryView, span: Span) -> HostResult<String> {
+    Ok(match span.len {
         0 => "".into(),
-        _ => WasmPtr::<u8>::new(span.ptr)
-            .read_utf8_string(view, span.len)
-            .unwrap(),
-    }
+        _ => WasmPtr::<u8>::new(span.ptr).read_utf8_string(view, span.len)?,
+    })
 }
 
 fn taca_bindings_apply(mut env: FunctionEnvMut<PartData>, bindings: u32) {
-    let part = env.data_mut();
-    let mut system = part.system.lock().unwrap();
-    bindings_apply(&mut system, bindings);
+    host_call(&mut env, "taca_bindings_apply", |system, _| {
+        bindings_apply(system, bindings)
+    })
 }
 
 fn taca_bindings_new(mut env: FunctionEnvMut<PartData>, bindings: u32) -> u32 {
     // TOOD Consider this more.
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let bindings = WasmPtr::<ExternBindingsInfo>::new(bindings)
-        .read(&view)
-        .unwrap();
-    let bindings = BindingsInfo {
-        pipeline: bindings.pipeline,
-        group_index: bindings.group_index,
-        buffers: read_span(&view, bindings.buffers),
-        samplers: read_span(&view, bindings.samplers),
-        textures: read_span(&view, bindings.textures),
-    };
-    bindings_new(&mut system, bindings);
-    system.bindings.len().try_into().unwrap()
+    host_call(&mut env, "taca_bindings_new", |system, view| {
+        let bindings = WasmPtr::<ExternBindingsInfo>::new(bindings).read(view)?;
+        let bindings = BindingsInfo {
+            pipeline: bindings.pipeline,
+            group_index: bindings.group_index,
+            buffers: read_span(view, bindings.buffers)?,
+            samplers: read_span(view, bindings.samplers)?,
+            textures: read_span(view, bindings.textures)?,
+        };
+        bindings_new(system, bindings)?;
+        Ok(system.bindings.len() as u32)
+    })
+}
+
+fn taca_buffer_drop(mut env: FunctionEnvMut<PartData>, buffer: u32) {
+    host_call(&mut env, "taca_buffer_drop", |system, _| {
+        system.buffers.remove(buffer)?;
+        Ok(())
+    })
 }
 
 fn taca_buffer_new(mut env: FunctionEnvMut<PartData>, kind: u32, slice: u32) -> u32 {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let slice = WasmPtr::<BufferSlice>::new(slice).read(&view).unwrap();
-    let contents = match slice.ptr {
-        0 => None,
-        _ => Some(
-            view.copy_range_to_vec(slice.ptr as u64..(slice.ptr + slice.size) as u64)
-                .unwrap(),
-        ),
-    };
-    match kind {
-        3 => system
-            .buffers
-            .push(Buffer::CpuBuffer(CpuBuffer { data: vec![] })),
-        _ => create_buffer(&mut system, contents.as_deref(), slice.size, kind),
-    }
-    system.buffers.len() as u32
+    host_call(&mut env, "taca_buffer_new", |system, view| {
+        let slice = WasmPtr::<BufferSlice>::new(slice).read(view)?;
+        let contents = match slice.ptr {
+            0 => None,
+            _ => Some(
+                view.copy_range_to_vec(slice.ptr as u64..slice.ptr as u64 + slice.size as u64)?,
+            ),
+        };
+        match kind {
+            3 => system
+                .buffers
+                .insert(Buffer::CpuBuffer(CpuBuffer { data: vec![] })),
+            _ => create_buffer(system, contents.as_deref(), slice.size, kind),
+        }
+    })
 }
 
 fn taca_buffer_read(mut env: FunctionEnvMut<PartData>, buffer: u32, bytes: u32, offset: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let bytes = WasmPtr::<Span>::new(bytes).read(&view).unwrap();
-    // TODO Also read gpu buffers.
-    let Some(buffer) = system
-        .buffers
-        .get(match buffer as usize {
-            0 => return,
-            ind => ind - 1,
-        })
-        .and_then(|it| it.cpu())
-    else {
-        return;
-    };
-    let offset = offset as usize;
-    let len = (bytes.len as usize).min(buffer.data.len() - offset);
-    view.write(bytes.ptr as u64, &buffer.data[offset..offset + len])
-        .unwrap();
+    host_call(&mut env, "taca_buffer_read", |system, view| {
+        let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+        // TODO Also read gpu buffers.
+        let buffer = system
+            .buffers
+            .get(buffer)?
+            .cpu()
+            .ok_or(HostError::BadHandle {
+                kind: "cpu buffer",
+                handle: buffer,
+            })?;
+        let offset = (offset as usize).min(buffer.data.len());
+        let len = (bytes.len as usize).min(buffer.data.len() - offset);
+        view.write(bytes.ptr as u64, &buffer.data[offset..offset + len])?;
+        Ok(())
+    })
 }
 
 fn taca_buffer_update(mut env: FunctionEnvMut<PartData>, buffer: u32, bytes: u32, offset: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let bytes = WasmPtr::<Span>::new(bytes).read(&view).unwrap();
-    let bytes = read_span::<u8>(&view, bytes);
-    if let Some(buffer) = system
-        .buffers
-        .get_mut(buffer as usize - 1)
-        .and_then(|it| it.cpu_mut())
-    {
-        // Cpu buffer.
-        let data = &mut buffer.data;
-        let offset = offset as usize;
-        let new_size = data.len().max(offset + bytes.len());
-        data.resize(new_size, 0);
-        data[offset..offset + bytes.len()].copy_from_slice(&bytes);
-        return;
-    }
-    buffer_update(&mut system, buffer, &bytes, offset);
+    host_call(&mut env, "taca_buffer_update", |system, view| {
+        let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+        let bytes = read_span::<u8>(view, bytes)?;
+        if let Some(buffer) = system.buffers.get_mut(buffer)?.cpu_mut() {
+            // Cpu buffer.
+            let data = &mut buffer.data;
+            let offset = offset as usize;
+            // Allow appending but not gaps, which could be huge.
+            if offset > data.len() {
+                let message = format!("update offset {offset} beyond buffer size {}", data.len());
+                return Err(HostError::BadValue(message));
+            }
+            let new_size = data.len().max(offset + bytes.len());
+            data.resize(new_size, 0);
+            data[offset..offset + bytes.len()].copy_from_slice(&bytes);
+            return Ok(());
+        }
+        buffer_update(system, buffer, &bytes, offset)
+    })
 }
 
 fn taca_buffers_apply(mut env: FunctionEnvMut<PartData>, bindings: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let bindings = WasmPtr::<ExternMeshBuffers>::new(bindings)
-        .read(&view)
-        .unwrap();
-    // TODO Reusable buffer to read into!
-    let vertex_buffers = read_span(&view, bindings.vertex_buffers);
-    let buffers = MeshBuffers {
-        vertex_buffers: &vertex_buffers,
-        index_buffer: bindings.index_buffer,
-    };
-    buffers_apply(&mut system, buffers);
+    host_call(&mut env, "taca_buffers_apply", |system, view| {
+        let bindings = WasmPtr::<ExternMeshBuffers>::new(bindings).read(view)?;
+        // TODO Reusable buffer to read into!
+        let vertex_buffers = read_span(view, bindings.vertex_buffers)?;
+        let buffers = MeshBuffers {
+            vertex_buffers: &vertex_buffers,
+            index_buffer: bindings.index_buffer,
+        };
+        buffers_apply(system, buffers)
+    })
 }
 
 fn taca_clip(mut env: FunctionEnvMut<PartData>, x: f32, y: f32, size_x: f32, size_y: f32) {
-    let part = env.data_mut();
-    let mut system = part.system.lock().unwrap();
-    let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        return;
-    };
-    let wgpu::SurfaceConfiguration { width, height, .. } = gfx.config;
-    pass_ensure(&mut system);
-    let Some(RenderFrame {
-        pass: Some(pass), ..
-    }) = &mut system.frame
-    else {
-        return;
-    };
-    // gfx.surface.get_current_texture().unwrap().texture.size();
-    let x = (x.round() as u32).clamp(0, width);
-    let y = (y.round() as u32).clamp(0, height);
-    pass.set_scissor_rect(
-        x,
-        y,
-        (size_x.round() as u32).clamp(0, width - x),
-        (size_y.round() as u32).clamp(0, height - y),
-    );
+    host_call(&mut env, "taca_clip", |system, _| {
+        pass_ensure(system);
+        let (width, height) = pass_size(system);
+        let Some(RenderFrame {
+            pass: Some(pass), ..
+        }) = &mut system.frame
+        else {
+            return Ok(());
+        };
+        // gfx.surface.get_current_texture().unwrap().texture.size();
+        let x = (x.round() as u32).clamp(0, width);
+        let y = (y.round() as u32).clamp(0, height);
+        pass.set_scissor_rect(
+            x,
+            y,
+            (size_x.round() as u32).clamp(0, width - x),
+            (size_y.round() as u32).clamp(0, height - y),
+        );
+        Ok(())
+    })
 }
 
 fn taca_draw(
@@ -577,252 +908,522 @@ fn taca_draw(
     item_count: u32,
     instance_count: u32,
 ) {
+    host_call(&mut env, "taca_draw", |system, _| {
+        pipelined_ensure(system)?;
+        // TODO Actually ensure we got buffers?
+        buffered_ensure(system)?;
+        bound_ensure(system)?;
+        let Some(RenderFrame {
+            pass: Some(pass), ..
+        }) = &mut system.frame
+        else {
+            return Ok(());
+        };
+        let item_end = item_begin
+            .checked_add(item_count)
+            .ok_or_else(|| HostError::BadValue(format!("item end {item_begin} + {item_count}")))?;
+        pass.draw_indexed(item_begin..item_end, 0, 0..instance_count);
+        Ok(())
+    })
+}
+
+fn taca_error_last(mut env: FunctionEnvMut<PartData>) -> u32 {
     let part = env.data_mut();
     let mut system = part.system.lock().unwrap();
-    pipelined_ensure(&mut system);
-    // TODO Actually ensure we got buffers?
-    buffered_ensure(&mut system);
-    bound_ensure(&mut system);
-    let Some(RenderFrame {
-        pass: Some(pass), ..
-    }) = &mut system.frame
-    else {
-        return;
-    };
-    pass.draw_indexed(item_begin..item_begin + item_count, 0, 0..instance_count);
+    // Clear on read, so apps can check after any sequence of calls.
+    take(&mut system.error_last) as u32
 }
 
 fn taca_image_decode(mut env: FunctionEnvMut<PartData>, bytes: u32) -> u32 {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let bytes = WasmPtr::<Span>::new(bytes).read(&view).unwrap();
-    let bytes = read_span(&view, bytes);
-    system.textures.push(Texture { data: None });
-    let handle = system.textures.len();
-    system.tasks_active += 1;
-    system
-        .worker
-        .as_ref()
-        .unwrap()
-        .send(WorkItem::ImageDecode { handle, bytes })
-        .unwrap();
-    handle.try_into().unwrap()
+    host_call(&mut env, "taca_image_decode", |system, view| {
+        let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+        let bytes = read_span(view, bytes)?;
+        let handle = system.textures.insert(Texture {
+            data: None,
+            depth: None,
+            failure: None,
+        })?;
+        let sent = work_send(system, WorkItem::ImageDecode { handle, bytes });
+        if sent.is_err() {
+            system.textures.remove(handle)?;
+        }
+        sent.map(|_| handle)
+    })
 }
 
 fn taca_key_event(mut env: FunctionEnvMut<PartData>, result: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    WasmPtr::<KeyEvent>::new(result)
-        .write(&view, system.key_event)
-        .unwrap();
+    host_call(&mut env, "taca_key_event", |system, view| {
+        WasmPtr::<KeyEvent>::new(result).write(view, system.key_event)?;
+        Ok(())
+    })
+}
+
+fn taca_pass_begin(mut env: FunctionEnvMut<PartData>, info: u32) {
+    host_call(&mut env, "taca_pass_begin", |system, view| {
+        let info = WasmPtr::<PassInfoExtern>::new(info).read(view)?;
+        pass_begin(system, &info)
+    })
 }
 
 fn taca_pipeline_apply(mut env: FunctionEnvMut<PartData>, pipeline: u32) {
-    let part = env.data_mut();
-    let mut system = part.system.lock().unwrap();
-    pipeline_apply(&mut system, pipeline);
+    host_call(&mut env, "taca_pipeline_apply", |system, _| {
+        pipeline_apply(system, pipeline)
+    })
+}
+
+fn taca_pipeline_drop(mut env: FunctionEnvMut<PartData>, pipeline: u32) {
+    host_call(&mut env, "taca_pipeline_drop", |system, _| {
+        system.pipelines.remove(pipeline)?;
+        Ok(())
+    })
 }
 
 fn taca_pipeline_new(mut env: FunctionEnvMut<PartData>, info: u32) -> u32 {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let info = WasmPtr::<ExternPipelineInfo>::new(info)
-        .read(&view)
-        .unwrap();
-    // dbg!(info);
-    // println!("{info:?}");
-    let vertex_attributes = read_span(&view, info.vertex_attributes);
-    let vertex_buffers = read_span(&view, info.vertex_buffers);
-    let info = PipelineInfo {
-        depth_test: info.depth_test,
-        fragment: PipelineShaderInfo {
-            entry_point: read_string(&view, info.fragment.entry_point),
-            shader: info.fragment.shader,
-        },
-        vertex: PipelineShaderInfo {
-            entry_point: read_string(&view, info.vertex.entry_point),
-            shader: info.vertex.shader,
-        },
-        vertex_attributes,
-        vertex_buffers,
-    };
-    // dbg!(&info);
-    create_pipeline(&mut system, info);
-    system.pipelines.len() as u32
+    host_call(&mut env, "taca_pipeline_new", |system, view| {
+        let info = WasmPtr::<ExternPipelineBaseInfo>::new(info).read(view)?;
+        pipeline_new(system, view, info.into())
+    })
+}
+
+fn taca_pipeline_new2(mut env: FunctionEnvMut<PartData>, info: u32) -> u32 {
+    host_call(&mut env, "taca_pipeline_new2", |system, view| {
+        let info = WasmPtr::<ExternPipelineInfo>::new(info).read(view)?;
+        pipeline_new(system, view, info)
+    })
 }
 
 fn taca_print(mut env: FunctionEnvMut<PartData>, text: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let text = WasmPtr::<Span>::new(text).read(&view).unwrap();
-    let text = read_string(&view, text);
-    println!("{text}");
+    host_call(&mut env, "taca_print", |_, view| {
+        let text = WasmPtr::<Span>::new(text).read(view)?;
+        let text = read_string(view, text)?;
+        println!("{text}");
+        Ok(())
+    })
+}
+
+fn taca_shader_drop(mut env: FunctionEnvMut<PartData>, shader: u32) {
+    host_call(&mut env, "taca_shader_drop", |system, _| {
+        system.shaders.remove(shader)?;
+        Ok(())
+    })
 }
 
 fn taca_shader_new(mut env: FunctionEnvMut<PartData>, bytes: u32) -> u32 {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let bytes = WasmPtr::<Span>::new(bytes).read(&view).unwrap();
-    let bytes = read_span(&view, bytes);
-    let shader = shader_create(&mut system, &bytes);
-    system.shaders.push(shader);
-    system.shaders.len() as u32
+    host_call(&mut env, "taca_shader_new", |system, view| {
+        let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+        let bytes = read_span(view, bytes)?;
+        let shader = shader_create(system, &bytes)?;
+        system.shaders.insert(shader)
+    })
 }
 
 fn taca_sound_decode(mut env: FunctionEnvMut<PartData>, bytes: u32) -> u32 {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let bytes = WasmPtr::<Span>::new(bytes).read(&view).unwrap();
-    let bytes = read_span(&view, bytes);
-    system.sounds.push(Sound { data: None });
-    let handle = system.sounds.len();
-    system.tasks_active += 1;
-    system
-        .worker
-        .as_ref()
-        .unwrap()
-        .send(WorkItem::SoundDecode { handle, bytes })
-        .unwrap();
-    handle.try_into().unwrap()
+    host_call(&mut env, "taca_sound_decode", |system, view| {
+        sound_decode_start(system, view, bytes, 0)
+    })
+}
+
+fn taca_sound_decode_mode(mut env: FunctionEnvMut<PartData>, bytes: u32, mode: u32) -> u32 {
+    host_call(&mut env, "taca_sound_decode_mode", |system, view| {
+        if mode > 2 {
+            return Err(HostError::BadValue(format!("sound decode mode {mode}")));
+        }
+        sound_decode_start(system, view, bytes, mode)
+    })
+}
+
+fn taca_sound_drop(mut env: FunctionEnvMut<PartData>, sound: u32) {
+    host_call(&mut env, "taca_sound_drop", |system, _| {
+        system.sounds.remove(sound)?;
+        Ok(())
+    })
+}
+
+fn taca_sound_info(mut env: FunctionEnvMut<PartData>, result: u32, sound: u32) {
+    host_call(&mut env, "taca_sound_info", |system, view| {
+        let info = system.sounds.get(sound)?.info;
+        WasmRef::<SoundInfoExtern>::new(view, result as u64).write(info)?;
+        Ok(())
+    })
+}
+
+fn taca_sound_panning_update(
+    mut env: FunctionEnvMut<PartData>,
+    play: u32,
+    panning: f32,
+    duration: f32,
+) {
+    sound_play_update(&mut env, "taca_sound_panning_update", play, |play| {
+        play.set_panning(panning_from_extern(panning), tween_from_extern(duration));
+    })
+}
+
+fn taca_sound_pause(mut env: FunctionEnvMut<PartData>, play: u32, duration: f32) {
+    sound_play_update(&mut env, "taca_sound_pause", play, |play| {
+        play.pause(tween_from_extern(duration));
+    })
 }
 
 fn taca_sound_play(mut env: FunctionEnvMut<PartData>, info: u32) -> u32 {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let info = WasmPtr::<SoundPlayInfoExtern>::new(info)
-        .read(&view)
-        .unwrap();
-    // dbg!(info);
-    let Some(mut data) = system
-        .sounds
-        .get(info.sound as usize - 1)
-        .and_then(|it| it.data.clone())
-    else {
-        // eprintln!("no sound");
-        return 0;
-    };
-    let Some(audio_manager) = &mut system.audio_manager else {
-        // eprintln!("no audio manager");
-        return 0;
-    };
-    if info.delay > 0.0 {
-        data.settings.start_time = StartTime::Delayed(Duration::from_secs_f32(info.delay));
-    }
-    match info.rate_kind {
-        0 => data.settings.playback_rate = PlaybackRate::Semitones(info.rate as f64).into(),
-        1 => data.settings.playback_rate = PlaybackRate::Factor(info.rate as f64).into(),
-        _ => {}
-    }
-    match info.volume_kind {
-        0 => data.settings.volume = kira::tween::Value::Fixed(Volume::Decibels(info.volume as f64)),
-        1 => {
-            data.settings.volume = kira::tween::Value::Fixed(Volume::Amplitude(info.volume as f64))
+    host_call(&mut env, "taca_sound_play", |system, view| {
+        let info = WasmPtr::<SoundPlayBaseInfoExtern>::new(info).read(view)?;
+        sound_play(system, info.into())
+    })
+}
+
+fn taca_sound_play2(mut env: FunctionEnvMut<PartData>, info: u32) -> u32 {
+    host_call(&mut env, "taca_sound_play2", |system, view| {
+        let info = WasmPtr::<SoundPlayInfoExtern>::new(info).read(view)?;
+        sound_play(system, info)
+    })
+}
+
+fn taca_sound_rate_update(
+    mut env: FunctionEnvMut<PartData>,
+    play: u32,
+    rate: f32,
+    rate_kind: u32,
+    duration: f32,
+) {
+    sound_play_update(&mut env, "taca_sound_rate_update", play, |play| {
+        if let Some(rate) = rate_from_extern(rate, rate_kind) {
+            play.set_playback_rate(rate, tween_from_extern(duration));
         }
-        _ => {}
-    }
-    let _play = match audio_manager.play(data) {
-        Ok(play) => play,
-        Err(err) => {
-            dbg!(err);
-            return 0;
+    })
+}
+
+fn taca_sound_resume(mut env: FunctionEnvMut<PartData>, play: u32, duration: f32) {
+    sound_play_update(&mut env, "taca_sound_resume", play, |play| {
+        play.resume(tween_from_extern(duration));
+    })
+}
+
+fn taca_sound_status(mut env: FunctionEnvMut<PartData>, sound: u32) -> u32 {
+    host_call(&mut env, "taca_sound_status", |system, _| {
+        let sound = system.sounds.get(sound)?;
+        Ok(TaskStatus::of(&sound.data, &sound.failure) as u32)
+    })
+}
+
+fn taca_sound_status_message(mut env: FunctionEnvMut<PartData>, sound: u32, bytes: u32) -> u32 {
+    host_call(&mut env, "taca_sound_status_message", |system, view| {
+        let failure = &system.sounds.get(sound)?.failure;
+        status_message_write(view, failure, bytes)
+    })
+}
+
+fn taca_sound_stop(mut env: FunctionEnvMut<PartData>, play: u32, duration: f32) {
+    sound_play_update(&mut env, "taca_sound_stop", play, |play| {
+        play.stop(tween_from_extern(duration));
+    })
+}
+
+fn taca_sound_stream_drop(mut env: FunctionEnvMut<PartData>, stream: u32) {
+    host_call(&mut env, "taca_sound_stream_drop", |system, _| {
+        if stream != 0 {
+            system.sound_streams.remove(stream)?;
         }
-    };
-    0
+        Ok(())
+    })
+}
+
+fn taca_sound_stream_new(mut env: FunctionEnvMut<PartData>, info: u32) -> u32 {
+    host_call(&mut env, "taca_sound_stream_new", |system, view| {
+        let info = WasmPtr::<SoundStreamInfoExtern>::new(info).read(view)?;
+        let destination = match info.track {
+            0 => OutputDestination::default(),
+            track => system.sound_tracks.get(track)?.into(),
+        };
+        let (stream, data) = SoundStream::new(&info, destination)?;
+        let Some(audio_manager) = &mut system.audio_manager else {
+            return Ok(0);
+        };
+        if let Err(err) = audio_manager.play(data) {
+            eprintln!("sound stream play failed: {err}");
+            return Ok(0);
+        }
+        system.sound_streams.insert(stream)
+    })
+}
+
+fn taca_sound_stream_space(mut env: FunctionEnvMut<PartData>, stream: u32) -> u32 {
+    host_call(&mut env, "taca_sound_stream_space", |system, _| {
+        if stream == 0 {
+            return Ok(0);
+        }
+        Ok(system.sound_streams.get(stream)?.space() as u32)
+    })
+}
+
+fn taca_sound_stream_write(mut env: FunctionEnvMut<PartData>, stream: u32, samples: u32) -> u32 {
+    host_call(&mut env, "taca_sound_stream_write", |system, view| {
+        if stream == 0 {
+            return Ok(0);
+        }
+        let stream = system.sound_streams.get(stream)?;
+        let samples = WasmPtr::<Span>::new(samples).read(view)?;
+        let samples = read_span::<f32>(view, samples)?;
+        Ok(stream.write(&samples) as u32)
+    })
+}
+
+fn taca_sound_track_drop(mut env: FunctionEnvMut<PartData>, track: u32) {
+    host_call(&mut env, "taca_sound_track_drop", |system, _| {
+        if track != 0 {
+            system.sound_tracks.remove(track)?;
+        }
+        Ok(())
+    })
+}
+
+fn taca_sound_track_new(mut env: FunctionEnvMut<PartData>, info: u32) -> u32 {
+    host_call(&mut env, "taca_sound_track_new", |system, view| {
+        let info = WasmPtr::<SoundTrackInfoExtern>::new(info).read(view)?;
+        let effects = read_span::<SoundEffectInfoExtern>(view, info.effects)?;
+        let parent = match info.parent {
+            0 => None,
+            parent => Some(system.sound_tracks.get(parent)?),
+        };
+        let builder = track_builder_from_extern(&info, &effects, parent)?;
+        let Some(audio_manager) = &mut system.audio_manager else {
+            return Ok(0);
+        };
+        let track = match audio_manager.add_sub_track(builder) {
+            Ok(track) => track,
+            Err(err) => {
+                eprintln!("sound track add failed: {err}");
+                return Ok(0);
+            }
+        };
+        system.sound_tracks.insert(track)
+    })
+}
+
+fn taca_sound_track_volume_update(
+    mut env: FunctionEnvMut<PartData>,
+    track: u32,
+    volume: f32,
+    volume_kind: u32,
+    duration: f32,
+) {
+    host_call(&mut env, "taca_sound_track_volume_update", |system, _| {
+        if track == 0 {
+            return Ok(());
+        }
+        let track = system.sound_tracks.get_mut(track)?;
+        if let Some(volume) = volume_from_extern(volume, volume_kind) {
+            track.set_volume(volume, tween_from_extern(duration));
+        }
+        Ok(())
+    })
+}
+
+fn taca_sound_volume_update(
+    mut env: FunctionEnvMut<PartData>,
+    play: u32,
+    volume: f32,
+    volume_kind: u32,
+    duration: f32,
+) {
+    sound_play_update(&mut env, "taca_sound_volume_update", play, |play| {
+        if let Some(volume) = volume_from_extern(volume, volume_kind) {
+            play.set_volume(volume, tween_from_extern(duration));
+        }
+    })
+}
+
+fn taca_storage_read(mut env: FunctionEnvMut<PartData>, key: u32, bytes: u32) -> u32 {
+    host_call(&mut env, "taca_storage_read", |system, view| {
+        let key = WasmPtr::<Span>::new(key).read(view)?;
+        let key = read_span::<u8>(view, key)?;
+        let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+        let Some(value) = system.storage.read(&key) else {
+            return Ok(0);
+        };
+        // Report the full size so the app can retry with a bigger buffer.
+        let len = (bytes.len as usize).min(value.len());
+        view.write(bytes.ptr as u64, &value[..len])?;
+        Ok(value.len() as u32)
+    })
+}
+
+fn taca_storage_write(mut env: FunctionEnvMut<PartData>, key: u32, bytes: u32) -> u32 {
+    host_call(&mut env, "taca_storage_write", |system, view| {
+        let key = WasmPtr::<Span>::new(key).read(view)?;
+        let key = read_span::<u8>(view, key)?;
+        let bytes = WasmPtr::<Span>::new(bytes).read(view)?;
+        let bytes = read_span::<u8>(view, bytes)?;
+        Ok(system.storage.write(&key, &bytes) as u32)
+    })
+}
+
+fn taca_task_event(mut env: FunctionEnvMut<PartData>, result: u32) {
+    host_call(&mut env, "taca_task_event", |system, view| {
+        WasmPtr::<TaskEvent>::new(result).write(view, system.task_event)?;
+        Ok(())
+    })
 }
 
 fn taca_text_align(mut env: FunctionEnvMut<PartData>, x: u32, y: u32) {
-    let part = env.data_mut();
-    let system = part.system.lock().unwrap();
-    let text_engine = system.text.clone().unwrap();
-    let mut text_engine = text_engine.lock().unwrap();
-    text_engine.align_x = to_text_align_x(x);
-    text_engine.align_y = to_text_align_y(y);
+    host_call(&mut env, "taca_text_align", |system, _| {
+        let text_engine = system.text.clone().ok_or(HostError::NotReady("text"))?;
+        let mut text_engine = text_engine.lock().unwrap();
+        text_engine.align_x = to_text_align_x(x);
+        text_engine.align_y = to_text_align_y(y);
+        Ok(())
+    })
 }
 
 fn taca_text_draw(mut env: FunctionEnvMut<PartData>, text: u32, x: f32, y: f32) {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let text = WasmPtr::<Span>::new(text).read(&view).unwrap();
-    let text = read_string(&view, text);
-    pass_ensure(&mut system);
-    let text_engine = system.text.clone().unwrap();
-    text_engine.lock().unwrap().draw(&mut system, &text, x, y);
+    host_call(&mut env, "taca_text_draw", |system, view| {
+        let text = WasmPtr::<Span>::new(text).read(view)?;
+        let text = read_string(view, text)?;
+        let text_engine = system.text.clone().ok_or(HostError::NotReady("text"))?;
+        pass_ensure(system);
+        text_engine.lock().unwrap().draw(system, &text, x, y);
+        Ok(())
+    })
 }
 
 fn taca_text_event(mut env: FunctionEnvMut<PartData>, result: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let system = part.system.lock().unwrap();
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let event = TextEvent {
-        buffer: system.text_buffer.try_into().unwrap(),
-        size: match system.text_buffer {
-            0 => 0,
-            ind => system
+    host_call(&mut env, "taca_text_event", |system, view| {
+        let event = TextEvent {
+            buffer: system.text_buffer,
+            size: system
                 .buffers
-                .get(ind - 1)
+                .get(system.text_buffer)
+                .ok()
                 .and_then(|it| it.cpu())
                 .map_or(0, |it| it.data.len().try_into().unwrap_or(0)),
-        },
-    };
-    WasmPtr::<TextEvent>::new(result)
-        .write(&view, event)
-        .unwrap();
+        };
+        WasmPtr::<TextEvent>::new(result).write(view, event)?;
+        Ok(())
+    })
+}
+
+fn taca_texture_drop(mut env: FunctionEnvMut<PartData>, texture: u32) {
+    host_call(&mut env, "taca_texture_drop", |system, _| {
+        system.textures.remove(texture)?;
+        Ok(())
+    })
 }
 
 fn taca_texture_info(mut env: FunctionEnvMut<PartData>, result: u32, texture: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let system = part.system.lock().unwrap();
-    let size = system.textures[texture as usize - 1].data.as_ref().map_or(
-        wgpu::Extent3d {
-            width: 0,
-            height: 0,
-            depth_or_array_layers: 0,
-        },
-        |x| x.size,
-    );
-    let info = TextureInfoExtern {
-        size: [size.width as f32, size.height as f32],
-    };
-    let view = part.memory.as_ref().unwrap().view(&store);
-    WasmRef::<TextureInfoExtern>::new(&view, result as u64)
-        .write(info)
-        .unwrap();
+    host_call(&mut env, "taca_texture_info", |system, view| {
+        let texture = system.textures.get(texture)?;
+        let size = texture.data.as_ref().map_or(
+            wgpu::Extent3d {
+                width: 0,
+                height: 0,
+                depth_or_array_layers: 0,
+            },
+            |x| x.size,
+        );
+        let info = TextureInfoExtern {
+            size: [size.width as f32, size.height as f32],
+        };
+        WasmRef::<TextureInfoExtern>::new(view, result as u64).write(info)?;
+        Ok(())
+    })
+}
+
+fn taca_texture_new(mut env: FunctionEnvMut<PartData>, info: u32) -> u32 {
+    host_call(&mut env, "taca_texture_new", |system, view| {
+        let info = WasmPtr::<TextureNewInfoExtern>::new(info).read(view)?;
+        let pixels = read_span::<u8>(view, info.pixels)?;
+        let data = texture_new(system, &info, &pixels)?;
+        system.textures.insert(Texture {
+            data: Some(data),
+            depth: None,
+            failure: None,
+        })
+    })
+}
+
+fn taca_texture_status(mut env: FunctionEnvMut<PartData>, texture: u32) -> u32 {
+    host_call(&mut env, "taca_texture_status", |system, _| {
+        let texture = system.textures.get(texture)?;
+        Ok(TaskStatus::of(&texture.data, &texture.failure) as u32)
+    })
+}
+
+fn taca_texture_status_message(mut env: FunctionEnvMut<PartData>, texture: u32, bytes: u32) -> u32 {
+    host_call(&mut env, "taca_texture_status_message", |system, view| {
+        let failure = &system.textures.get(texture)?.failure;
+        status_message_write(view, failure, bytes)
+    })
+}
+
+fn taca_texture_target_new(mut env: FunctionEnvMut<PartData>, width: u32, height: u32) -> u32 {
+    host_call(&mut env, "taca_texture_target_new", |system, _| {
+        let texture = texture_target_new(system, width, height)?;
+        system.textures.insert(texture)
+    })
+}
+
+fn taca_texture_update(
+    mut env: FunctionEnvMut<PartData>,
+    texture: u32,
+    pixels: u32,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) {
+    host_call(&mut env, "taca_texture_update", |system, view| {
+        let pixels = WasmPtr::<Span>::new(pixels).read(view)?;
+        let pixels = read_span::<u8>(view, pixels)?;
+        let origin = wgpu::Origin3d { x, y, z: 0 };
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        texture_update(system, texture, origin, size, &pixels)
+    })
 }
 
 fn taca_title_update(mut env: FunctionEnvMut<PartData>, text: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        return;
-    };
-    let view = part.memory.as_ref().unwrap().view(&store);
-    let title = WasmPtr::<Span>::new(text).read(&view).unwrap();
-    let title = read_string(&view, title);
-    gfx.window.as_ref().set_title(&title);
+    host_call(&mut env, "taca_title_update", |system, view| {
+        let MaybeGraphics::Graphics(Graphics {
+            window: Some(window),
+            ..
+        }) = &mut system.display.graphics
+        else {
+            return Ok(());
+        };
+        let title = WasmPtr::<Span>::new(text).read(view)?;
+        let title = read_string(view, title)?;
+        window.set_title(&title);
+        Ok(())
+    })
 }
 
 fn taca_window_state(mut env: FunctionEnvMut<PartData>, result: u32) {
-    let (part, store) = env.data_and_store_mut();
-    let mut system = part.system.lock().unwrap();
-    let pointer = system.display.pointer_pos.unwrap_or(Default::default());
-    let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        return;
-    };
-    let size = gfx.window.inner_size();
-    let state = WindowState {
-        pointer: [pointer.x as f32, pointer.y as f32],
-        press: system.display.pointer_press,
-        size: [size.width as f32, size.height as f32],
-    };
-    let view = part.memory.as_ref().unwrap().view(&store);
-    WasmRef::<WindowState>::new(&view, result as u64)
-        .write(state)
-        .unwrap();
+    host_call(&mut env, "taca_window_state", |system, view| {
+        let pointer = system.display.pointer_pos.unwrap_or(Default::default());
+        let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
+            return Ok(());
+        };
+        let size = gfx.size();
+        let state = WindowState {
+            pointer: [pointer.x as f32, pointer.y as f32],
+            press: system.display.pointer_press,
+            size: [size.width as f32, size.height as f32],
+        };
+        WasmRef::<WindowState>::new(view, result as u64).write(state)?;
+        Ok(())
+    })
+}
+
+/// Queues background work, counted as active until its result comes back.
+fn work_send(system: &mut System, item: WorkItem) -> HostResult<()> {
+    system
+        .worker
+        .as_ref()
+        .ok_or(HostError::NotReady("worker"))?
+        .send(item)
+        .map_err(|_| HostError::NotReady("worker"))?;
+    system.tasks_active += 1;
+    Ok(())
 }
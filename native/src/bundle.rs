@@ -0,0 +1,430 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use wasmer::{ExternType, Module, Store};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+use crate::{InspectArgs, PackArgs};
+
+pub struct Bundle {
+    /// Other entries by name, such as assets.
+    pub files: BTreeMap<String, Vec<u8>>,
+    /// Stable app id from the "app.id" entry, if packed with one.
+    pub id: Option<String>,
+    /// Extensions in load order followed by the app itself.
+    pub parts: Vec<BundlePart>,
+}
+
+pub struct BundlePart {
+    pub name: String,
+    pub wasm: Vec<u8>,
+}
+
+impl Bundle {
+    pub fn read(path: &str) -> Result<Bundle, String> {
+        let buf = fs::read(path).map_err(|err| format!("{path}: {err}"))?;
+        if buf.first() != Some(&0x50) {
+            // Not a zip, so presume a lone app wasm.
+            let wasm = BundlePart {
+                name: "app.wasm".into(),
+                wasm: buf,
+            };
+            return Ok(Bundle {
+                files: BTreeMap::new(),
+                id: None,
+                parts: vec![wasm],
+            });
+        }
+        let mut files = BTreeMap::new();
+        let mut id = None;
+        let mut parts = vec![];
+        let mut zip = ZipArchive::new(Cursor::new(buf)).map_err(|err| format!("{path}: {err}"))?;
+        let read = |file: &mut dyn Read, name: &str| -> Result<Vec<u8>, String> {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)
+                .map_err(|err| format!("{path}: {name}: {err}"))?;
+            Ok(bytes)
+        };
+        // Read extensions and other files.
+        for i in 0..zip.len() {
+            let mut file = zip.by_index(i).map_err(|err| format!("{path}: {err}"))?;
+            let name = file.name().to_string();
+            if file.is_dir() || name == "app.wasm" {
+                continue;
+            }
+            let is_ext = name
+                .strip_prefix("ext/")
+                .is_some_and(|ext_name| !ext_name.contains('/') && ext_name.ends_with(".wasm"));
+            let bytes = read(&mut file, &name)?;
+            if name == "app.id" {
+                let text = String::from_utf8(bytes).map_err(|_| format!("{path}: bad app.id"))?;
+                id_check(&text).map_err(|err| format!("{path}: {err}"))?;
+                id = Some(text);
+                continue;
+            }
+            match is_ext {
+                true => parts.push(BundlePart { name, wasm: bytes }),
+                false => {
+                    files.insert(name, bytes);
+                }
+            }
+        }
+        // Read app.
+        let mut file = zip
+            .by_name("app.wasm")
+            .map_err(|err| format!("{path}: app.wasm: {err}"))?;
+        let wasm = read(&mut file, "app.wasm")?;
+        parts.push(BundlePart {
+            name: "app.wasm".into(),
+            wasm,
+        });
+        Ok(Bundle { files, id, parts })
+    }
+
+    /// Names per-app storage by id, else by a hash of app.wasm, as on web.
+    pub fn storage_name(&self) -> String {
+        if let Some(id) = &self.id {
+            return id.clone();
+        }
+        let app = self.parts.last().unwrap();
+        let hash: String = Sha256::digest(&app.wasm)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        format!("sha256-{hash}")
+    }
+}
+
+/// Functions provided to parts under the "env" module, not counting bonus
+/// exports from extensions.
+pub const ENV_IMPORTS: &[&str] = &[
+    "taca_bindings_apply",
+    "taca_bindings_new",
+    "taca_buffer_drop",
+    "taca_buffer_new",
+    "taca_buffer_read",
+    "taca_buffer_update",
+    "taca_buffers_apply",
+    "taca_clip",
+    "taca_draw",
+    "taca_error_last",
+    "taca_image_decode",
+    "taca_key_event",
+    "taca_pass_begin",
+    "taca_pipeline_apply",
+    "taca_pipeline_drop",
+    "taca_pipeline_new",
+    "taca_pipeline_new2",
+    "taca_print",
+    "taca_shader_drop",
+    "taca_shader_new",
+    "taca_sound_decode",
+    "taca_sound_decode_mode",
+    "taca_sound_drop",
+    "taca_sound_info",
+    "taca_sound_panning_update",
+    "taca_sound_pause",
+    "taca_sound_play",
+    "taca_sound_play2",
+    "taca_sound_rate_update",
+    "taca_sound_resume",
+    "taca_sound_status",
+    "taca_sound_status_message",
+    "taca_sound_stop",
+    "taca_sound_stream_drop",
+    "taca_sound_stream_new",
+    "taca_sound_stream_space",
+    "taca_sound_stream_write",
+    "taca_sound_track_drop",
+    "taca_sound_track_new",
+    "taca_sound_track_volume_update",
+    "taca_sound_volume_update",
+    "taca_storage_read",
+    "taca_storage_write",
+    "taca_task_event",
+    "taca_text_align",
+    "taca_text_draw",
+    "taca_text_event",
+    "taca_texture_drop",
+    "taca_texture_info",
+    "taca_texture_new",
+    "taca_texture_status",
+    "taca_texture_status_message",
+    "taca_texture_target_new",
+    "taca_texture_update",
+    "taca_title_update",
+    "taca_window_state",
+];
+
+pub const WASI_IMPORTS: &[&str] = &[
+    "args_get",
+    "args_sizes_get",
+    "clock_res_get",
+    "clock_time_get",
+    "fd_close",
+    "fd_fdstat_get",
+    "fd_filestat_get",
+    "fd_prestat_dir_name",
+    "fd_prestat_get",
+    "fd_read",
+    "fd_readdir",
+    "fd_seek",
+    "fd_write",
+    "path_filestat_get",
+    "path_open",
+    "proc_exit",
+    "random_get",
+];
+
+/// Extension exports that get imported into later parts.
+pub fn is_bonus_export(key: &str) -> bool {
+    match key {
+        "init" | "initialize" | "start" | "update" => false,
+        _ if key.starts_with('_') || key.starts_with("taca_") || key.contains('.') => false,
+        _ => true,
+    }
+}
+
+/// Finds reasons the module would fail to instantiate as a part.
+pub fn part_problems(module: &Module, bonus_exports: &HashSet<String>) -> Vec<String> {
+    let mut problems = vec![];
+    let has_memory = module
+        .exports()
+        .any(|export| export.name() == "memory" && matches!(export.ty(), ExternType::Memory(_)));
+    if !has_memory {
+        problems.push("missing memory export".into());
+    }
+    for import in module.imports() {
+        let (namespace, name) = (import.module(), import.name());
+        let known = match namespace {
+            "env" => ENV_IMPORTS.contains(&name) || bonus_exports.contains(name),
+            "wasi_snapshot_preview1" => WASI_IMPORTS.contains(&name),
+            _ => false,
+        };
+        if !known {
+            problems.push(format!("unknown import {namespace}.{name}"));
+        }
+    }
+    problems
+}
+
+pub fn inspect(args: &InspectArgs) -> Result<(), String> {
+    let bundle = Bundle::read(&args.app)?;
+    println!("storage name: {}", bundle.storage_name());
+    let store = Store::default();
+    let mut bonus_exports = HashSet::new();
+    let ext_count = bundle.parts.len() - 1;
+    for (index, part) in bundle.parts.iter().enumerate() {
+        let module =
+            Module::new(&store, &part.wasm).map_err(|err| format!("{}: {err}", part.name))?;
+        let is_ext = index < ext_count;
+        println!("{}", part.name);
+        let mut exports = vec![];
+        let mut has_memory = false;
+        for export in module.exports() {
+            if export.name() == "memory" && matches!(export.ty(), ExternType::Memory(_)) {
+                has_memory = true;
+            }
+            if is_ext && is_bonus_export(export.name()) {
+                exports.push(export.name().to_string());
+            }
+        }
+        let mut taca_imports = vec![];
+        let mut wasi_imports = vec![];
+        let mut bonus_imports = vec![];
+        let mut missing_imports = vec![];
+        for import in module.imports() {
+            let (namespace, name) = (import.module(), import.name());
+            match namespace {
+                "env" if ENV_IMPORTS.contains(&name) => taca_imports.push(name.to_string()),
+                "env" if !is_ext && bonus_exports.contains(name) => {
+                    bonus_imports.push(name.to_string())
+                }
+                "wasi_snapshot_preview1" if WASI_IMPORTS.contains(&name) => {
+                    wasi_imports.push(name.to_string())
+                }
+                _ => missing_imports.push(format!("{namespace}.{name}")),
+            }
+        }
+        if !has_memory {
+            println!("  missing memory export");
+        }
+        let list = |label: &str, items: &[String]| {
+            if !items.is_empty() {
+                println!("  {label}: {}", items.join(", "));
+            }
+        };
+        list("bonus exports", &exports);
+        list("taca imports", &taca_imports);
+        list("wasi imports", &wasi_imports);
+        list("bonus imports", &bonus_imports);
+        list("missing imports", &missing_imports);
+        if is_ext {
+            bonus_exports.extend(exports);
+        }
+    }
+    Ok(())
+}
+
+pub fn pack(args: &PackArgs) -> Result<(), String> {
+    let store = Store::default();
+    let read = |path: &str| fs::read(path).map_err(|err| format!("{path}: {err}"));
+    // Validate parts in load order so extension exports are available later.
+    let mut bonus_exports = HashSet::new();
+    let mut exts = vec![];
+    for path in &args.ext {
+        let wasm = read(path)?;
+        let module = Module::new(&store, &wasm).map_err(|err| format!("{path}: {err}"))?;
+        problems_check(path, part_problems(&module, &HashSet::new()))?;
+        bonus_exports.extend(
+            module
+                .exports()
+                .map(|export| export.name().to_string())
+                .filter(|name| is_bonus_export(name)),
+        );
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("{path}: bad file name"))?;
+        exts.push((format!("ext/{name}"), wasm));
+    }
+    if let Some(id) = &args.id {
+        id_check(id)?;
+    }
+    let app = read(&args.app)?;
+    let module = Module::new(&store, &app).map_err(|err| format!("{}: {err}", args.app))?;
+    problems_check(&args.app, part_problems(&module, &bonus_exports))?;
+    // Gather assets.
+    let mut assets = vec![];
+    if let Some(dir) = &args.assets {
+        assets_find(Path::new(dir), "", &mut assets)?;
+        assets.sort();
+        if let Some((name, _)) = assets
+            .iter()
+            .find(|(name, _)| name == "app.id" || name == "app.wasm" || name.starts_with("ext/"))
+        {
+            return Err(format!("{dir}: reserved asset name {name}"));
+        }
+    }
+    // Write bundle.
+    let file = File::create(&args.output).map_err(|err| format!("{}: {err}", args.output))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+    let mut write = |name: &str, bytes: &[u8]| -> Result<(), String> {
+        zip.start_file(name, options)
+            .map_err(|err| err.to_string())?;
+        zip.write_all(bytes).map_err(|err| err.to_string())
+    };
+    for (name, wasm) in &exts {
+        write(name, wasm)?;
+    }
+    write("app.wasm", &app)?;
+    if let Some(id) = &args.id {
+        write("app.id", id.as_bytes())?;
+    }
+    for (name, path) in &assets {
+        let bytes = fs::read(path).map_err(|err| format!("{path:?}: {err}"))?;
+        write(name, &bytes)?;
+    }
+    zip.finish().map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+fn assets_find(
+    dir: &Path,
+    prefix: &str,
+    assets: &mut Vec<(String, PathBuf)>,
+) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("{dir:?}: {err}"))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| format!("{dir:?}: {err}"))?;
+        let path = entry.path();
+        let Some(name) = entry.file_name().to_str().map(|it| format!("{prefix}{it}")) else {
+            return Err(format!("{path:?}: bad file name"));
+        };
+        if path.is_dir() {
+            assets_find(&path, &format!("{name}/"), assets)?;
+        } else {
+            assets.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+/// Keeps ids safe as storage dir names, matching web checks.
+fn id_check(id: &str) -> Result<(), String> {
+    let ok = (1..=64).contains(&id.len())
+        && !id.starts_with('.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    match ok {
+        true => Ok(()),
+        false => Err(format!("bad app id {id:?}")),
+    }
+}
+
+fn problems_check(path: &str, problems: Vec<String>) -> Result<(), String> {
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(format!("{path}: {}", problems.join(", "))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_ids_fail() {
+        for id in ["hi", "com.example.app-1_x", &"a".repeat(64)] {
+            assert!(id_check(id).is_ok(), "{id}");
+        }
+        for id in ["", ".hidden", "a/b", "a b", "é", &"a".repeat(65)] {
+            assert!(id_check(id).is_err(), "{id}");
+        }
+    }
+
+    #[test]
+    fn reserved_exports_stay_home() {
+        for key in [
+            "init",
+            "initialize",
+            "start",
+            "update",
+            "_start",
+            "taca_x",
+            "a.b",
+        ] {
+            assert!(!is_bonus_export(key), "{key}");
+        }
+        assert!(is_bonus_export("helper"));
+    }
+
+    #[test]
+    fn unknown_imports_are_problems() {
+        let wat = r#"(module
+            (import "env" "taca_print" (func (param i32)))
+            (import "env" "helper" (func))
+            (import "env" "mystery" (func))
+            (import "wasi_snapshot_preview1" "random_get" (func (param i32 i32) (result i32)))
+            (import "other" "thing" (func))
+            (memory (export "memory") 1))"#;
+        let module = Module::new(&Store::default(), wat).unwrap();
+        let bonus_exports = HashSet::from(["helper".to_string()]);
+        assert_eq!(
+            part_problems(&module, &bonus_exports),
+            ["unknown import env.mystery", "unknown import other.thing"]
+        );
+        let module = Module::new(&Store::default(), "(module)").unwrap();
+        assert_eq!(
+            part_problems(&module, &HashSet::new()),
+            ["missing memory export"]
+        );
+    }
+}
@@ -1,5 +1,4 @@
 use image::{DynamicImage, ImageResult};
-use kira::sound::static_sound::StaticSoundData;
 use std::{
     future::Future,
     ptr::null_mut,
@@ -19,16 +18,22 @@ use winit::{
 };
 
 use crate::{
-    app::AppPtr,
+    app::{AppPtr, Halt},
     gpu::TextureData,
     key::{Key, KeyEvent},
+    replay::{Input, Recorder, Replay},
+    sound::{SoundInfoExtern, SoundSource},
 };
 
 pub struct Display {
     pub app: AppPtr, // TODO Arc<Mutex<App>>?
+    /// Count of frames drawn so far.
+    frame: u64,
     pub graphics: MaybeGraphics,
     pub pointer_pos: Option<PhysicalPosition<f64>>,
     pub pointer_press: u32,
+    pub recorder: Option<Recorder>,
+    pub replay: Option<Replay>,
     time_end: Instant,
     time_mean: f64,
     time_report: Instant,
@@ -43,6 +48,8 @@ pub enum EventKind {
     Press = 3,
     Release = 4,
     Text = 5,
+    TaskFailed = 6,
+    TaskDone = 7,
 }
 
 const REPORT_DELAY: Duration = Duration::from_secs(10);
@@ -54,14 +61,26 @@ pub struct DisplayOptions {
 
 impl Display {
     pub fn new(event_loop: &EventLoop<UserEvent>, options: DisplayOptions) -> Self {
+        Self::with_graphics(MaybeGraphics::Builder(GraphicsBuilder::new(
+            event_loop.create_proxy(),
+            options,
+        )))
+    }
+
+    /// For use without a window or event loop.
+    pub fn headless(graphics: Graphics) -> Self {
+        Self::with_graphics(MaybeGraphics::Graphics(graphics))
+    }
+
+    fn with_graphics(graphics: MaybeGraphics) -> Self {
         Self {
             app: AppPtr(null_mut()),
-            graphics: MaybeGraphics::Builder(GraphicsBuilder::new(
-                event_loop.create_proxy(),
-                options,
-            )),
+            frame: 0,
+            graphics,
             pointer_pos: None,
             pointer_press: 0,
+            recorder: None,
+            replay: None,
             time_end: Instant::now(),
             time_mean: 0.0,
             time_report: Instant::now() + REPORT_DELAY,
@@ -69,13 +88,24 @@ impl Display {
     }
 
     fn draw(&mut self) {
-        let MaybeGraphics::Graphics(gfx) = &mut self.graphics else {
+        if let MaybeGraphics::Builder(_) = self.graphics {
             // draw call rejected because graphics doesn't exist yet
             return;
-        };
+        }
+        // Replay inputs as of this frame before drawing.
+        while let Some(input) = self.replay.as_mut().and_then(|it| it.next(self.frame)) {
+            self.input_apply(input);
+        }
         // TODO Event.
         unsafe { &mut *self.app.0 }.listen();
-        gfx.window.request_redraw();
+        self.frame += 1;
+        if let MaybeGraphics::Graphics(Graphics {
+            window: Some(window),
+            ..
+        }) = &self.graphics
+        {
+            window.request_redraw();
+        }
         let elapsed = self.time_end.elapsed();
         let target_elapsed = Duration::from_secs_f64(1.0 / 60.0);
         if elapsed < target_elapsed {
@@ -91,14 +121,61 @@ impl Display {
         }
     }
 
+    pub fn input_apply(&mut self, input: Input) {
+        let app = unsafe { &mut *self.app.0 };
+        match input {
+            Input::Key { pressed, key, text } => {
+                {
+                    let mut system = app.system.lock().unwrap();
+                    system.key_event = KeyEvent {
+                        pressed,
+                        key,
+                        modifiers: 0,
+                    };
+                    if let Some(text) = &text {
+                        system.update_text_buffer(text);
+                    }
+                }
+                app.parts_update(EventKind::Key);
+                if text.is_some() {
+                    app.parts_update(EventKind::Text);
+                }
+            }
+            Input::PointerMove { x, y } => {
+                self.pointer_pos = Some(PhysicalPosition::new(x, y));
+            }
+            Input::Press { button } => {
+                self.pointer_press |= button;
+                app.parts_update(EventKind::Press);
+            }
+            Input::Release { button } => {
+                self.pointer_press &= !button;
+                app.parts_update(EventKind::Release);
+            }
+        }
+    }
+
+    /// Records or applies live input, except during replay.
+    fn input_live(&mut self, input: Input) {
+        if self.replay.as_ref().is_some_and(|it| !it.is_done()) {
+            return;
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.write(self.frame, &input);
+        }
+        self.input_apply(input);
+    }
+
     fn resized(&mut self, size: PhysicalSize<u32>) {
         let MaybeGraphics::Graphics(gfx) = &mut self.graphics else {
             return;
         };
         gfx.config.width = size.width;
         gfx.config.height = size.height;
-        gfx.surface.configure(&gfx.device, &gfx.config);
-        gfx.depth_texture = create_depth_texture(&gfx.device, &gfx.config);
+        if let Some(surface) = &gfx.surface {
+            surface.configure(&gfx.device, &gfx.config);
+        }
+        gfx.depth_texture = create_depth_texture(&gfx.device, size.width, size.height);
     }
 
     pub fn run(&mut self, event_loop: EventLoop<UserEvent>) {
@@ -107,13 +184,23 @@ impl Display {
 }
 
 impl ApplicationHandler<UserEvent> for Display {
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(Halt::Exit(_)) = unsafe { &*self.app.0 }.halt {
+            event_loop.exit();
+        }
+    }
+
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
         _window_id: WindowId,
         event: WindowEvent,
     ) {
-        let MaybeGraphics::Graphics(gfx) = &mut self.graphics else {
+        let MaybeGraphics::Graphics(Graphics {
+            window: Some(window),
+            ..
+        }) = &mut self.graphics
+        else {
             // draw call rejected because graphics doesn't exist yet
             return;
         };
@@ -136,11 +223,11 @@ impl ApplicationHandler<UserEvent> for Display {
                 PhysicalKey::Code(key) => match key {
                     KeyCode::F11 => {
                         if state.is_pressed() && !repeat {
-                            let fullscreen = match gfx.window.fullscreen() {
+                            let fullscreen = match window.fullscreen() {
                                 Some(_) => None,
                                 None => Some(Fullscreen::Borderless(None)),
                             };
-                            gfx.window.set_fullscreen(fullscreen);
+                            window.set_fullscreen(fullscreen);
                         }
                     }
                     KeyCode::F12 => {
@@ -149,39 +236,30 @@ impl ApplicationHandler<UserEvent> for Display {
                         // TODO Instead do Cmd+Option+I / Ctrl+Shift+I?
                     }
                     _ if !repeat => {
-                        let app = unsafe { &mut *self.app.0 };
                         let key: Key = key.into();
-                        let key = key as i32;
                         let pressed = state.is_pressed();
-                        let mut has_text = false;
-                        {
-                            let mut system = app.system.lock().unwrap();
-                            system.key_event = KeyEvent {
-                                pressed,
-                                key,
-                                modifiers: 0,
-                            };
-                            if pressed {
-                                if let Some(text) = &text {
-                                    let text = text.as_str();
-                                    if !(text.len() == 1 && text.chars().next().unwrap() < '\x20') {
-                                        has_text = true;
-                                        system.update_text_buffer(text);
-                                    }
-                                }
-                            }
-                        }
-                        app.parts_update(EventKind::Key);
-                        if has_text {
-                            app.parts_update(EventKind::Text);
-                        }
+                        let text = text
+                            .filter(|text| {
+                                let text = text.as_str();
+                                pressed
+                                    && !(text.len() == 1 && text.chars().next().unwrap() < '\x20')
+                            })
+                            .map(|text| text.to_string());
+                        self.input_live(Input::Key {
+                            pressed,
+                            key: key as i32,
+                            text,
+                        });
                     }
                     _ => {}
                 },
                 PhysicalKey::Unidentified(_) => {}
             },
             WindowEvent::CursorMoved { position, .. } => {
-                self.pointer_pos = Some(position);
+                self.input_live(Input::PointerMove {
+                    x: position.x,
+                    y: position.y,
+                });
             }
             WindowEvent::MouseInput { state, button, .. } => {
                 // TODO Consider device_id?
@@ -194,18 +272,11 @@ impl ApplicationHandler<UserEvent> for Display {
                         0
                     }
                 };
-                let kind = match state {
-                    winit::event::ElementState::Pressed => {
-                        self.pointer_press |= bit;
-                        EventKind::Press
-                    }
-                    winit::event::ElementState::Released => {
-                        self.pointer_press &= !bit;
-                        EventKind::Release
-                    }
+                let input = match state {
+                    winit::event::ElementState::Pressed => Input::Press { button: bit },
+                    winit::event::ElementState::Released => Input::Release { button: bit },
                 };
-                let app = unsafe { &mut *self.app.0 };
-                app.parts_update(kind);
+                self.input_live(input);
             }
             WindowEvent::Resized(size) => self.resized(size),
             WindowEvent::RedrawRequested => self.draw(),
@@ -223,7 +294,9 @@ impl ApplicationHandler<UserEvent> for Display {
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
         match event {
             UserEvent::Graphics(graphics) => {
-                graphics.window.as_ref().set_title("Taca");
+                if let Some(window) = &graphics.window {
+                    window.set_title("Taca");
+                }
                 self.graphics = MaybeGraphics::Graphics(graphics);
                 let MaybeGraphics::Graphics(gfx) = &self.graphics else {
                     panic!()
@@ -239,27 +312,38 @@ impl ApplicationHandler<UserEvent> for Display {
 pub enum UserEvent {
     Graphics(Graphics),
     ImageDecoded {
-        handle: usize,
+        handle: u32,
         image: ImageResult<DynamicImage>,
     },
     SoundDecoded {
-        handle: usize,
-        sound: Box<Result<StaticSoundData, kira::sound::FromFileError>>,
+        handle: u32,
+        sound: Box<Result<(SoundSource, SoundInfoExtern), kira::sound::FromFileError>>,
     },
 }
 
 #[derive(Debug)]
 pub struct Graphics {
-    pub window: Arc<Window>,
+    pub window: Option<Arc<Window>>,
     pub config: SurfaceConfiguration,
     pub depth_texture: TextureData,
     pub instance: Instance,
-    pub surface: Surface<'static>,
+    /// Either surface or offscreen should be present for rendering into.
+    pub surface: Option<Surface<'static>>,
+    pub offscreen: Option<TextureData>,
     pub adapter: Adapter,
     pub device: Device,
     pub queue: Queue,
 }
 
+impl Graphics {
+    pub fn size(&self) -> PhysicalSize<u32> {
+        match &self.window {
+            Some(window) => window.inner_size(),
+            None => PhysicalSize::new(self.config.width, self.config.height),
+        }
+    }
+}
+
 pub struct GraphicsBuilder {
     event_loop_proxy: Option<EventLoopProxy<UserEvent>>,
     options: DisplayOptions,
@@ -298,13 +382,10 @@ pub struct WindowState {
     pub size: [f32; 2],
 }
 
-pub fn create_depth_texture(
-    device: &wgpu::Device,
-    config: &wgpu::SurfaceConfiguration,
-) -> TextureData {
+pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> TextureData {
     let size = wgpu::Extent3d {
-        width: config.width.max(1),
-        height: config.height.max(1),
+        width: width.max(1),
+        height: height.max(1),
         depth_or_array_layers: 1,
     };
     let desc = wgpu::TextureDescriptor {
@@ -367,17 +448,98 @@ fn create_graphics(
         config.view_formats = vec![TextureFormat::Bgra8UnormSrgb, TextureFormat::Bgra8Unorm];
         // dbg!(&config);
         surface.configure(&device, &config);
-        let depth_texture = create_depth_texture(&device, &config);
+        let depth_texture = create_depth_texture(&device, config.width, config.height);
 
         Graphics {
-            window,
+            window: Some(window),
             config,
             depth_texture,
             instance,
-            surface,
+            surface: Some(surface),
+            offscreen: None,
             adapter,
             device,
             queue,
         }
     }
 }
+
+/// Renders to an offscreen texture, optionally on a software adapter.
+pub async fn create_graphics_headless(
+    width: u32,
+    height: u32,
+    fallback: bool,
+) -> Result<Graphics, String> {
+    let instance = wgpu::Instance::default();
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: None,
+            power_preference: wgpu::PowerPreference::None,
+            force_fallback_adapter: fallback,
+        })
+        .await
+        .ok_or("no gpu adapter found")?;
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                memory_hints: wgpu::MemoryHints::Performance,
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits::default(),
+            },
+            None,
+        )
+        .await
+        .map_err(|err| format!("gpu device: {err}"))?;
+    // Not used for any surface, but keeps sizes in the usual place.
+    let config = SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format: TextureFormat::Bgra8Unorm,
+        width,
+        height,
+        present_mode: wgpu::PresentMode::Fifo,
+        desired_maximum_frame_latency: 2,
+        alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        view_formats: vec![],
+    };
+    let depth_texture = create_depth_texture(&device, config.width, config.height);
+    let offscreen = create_offscreen_texture(&device, &config);
+    Ok(Graphics {
+        window: None,
+        config,
+        depth_texture,
+        instance,
+        surface: None,
+        offscreen: Some(offscreen),
+        adapter,
+        device,
+        queue,
+    })
+}
+
+fn create_offscreen_texture(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+) -> TextureData {
+    let size = wgpu::Extent3d {
+        width: config.width.max(1),
+        height: config.height.max(1),
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TextureFormat::Bgra8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    TextureData {
+        size,
+        texture,
+        view,
+    }
+}
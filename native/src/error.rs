@@ -0,0 +1,63 @@
+use std::fmt;
+
+use wasmer::MemoryAccessError;
+
+/// Failures from bad app input, reported through taca_error_last rather than
+/// taking down the host.
+#[derive(Debug)]
+pub enum HostError {
+    BadHandle { kind: &'static str, handle: u32 },
+    BadMemory(MemoryAccessError),
+    BadValue(String),
+    NotReady(&'static str),
+}
+
+/// Error codes as seen by apps, where zero means no error.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(u32)]
+pub enum ErrorCode {
+    #[default]
+    None = 0,
+    BadHandle,
+    BadMemory,
+    BadValue,
+    NotReady,
+}
+
+impl HostError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            HostError::BadHandle { .. } => ErrorCode::BadHandle,
+            HostError::BadMemory(_) => ErrorCode::BadMemory,
+            HostError::BadValue(_) => ErrorCode::BadValue,
+            HostError::NotReady(_) => ErrorCode::NotReady,
+        }
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HostError::BadHandle { kind, handle } => write!(f, "bad {kind} handle {handle}"),
+            HostError::BadMemory(err) => write!(f, "bad memory access: {err}"),
+            HostError::BadValue(message) => write!(f, "bad value: {message}"),
+            HostError::NotReady(what) => write!(f, "{what} not ready"),
+        }
+    }
+}
+
+impl From<MemoryAccessError> for HostError {
+    fn from(err: MemoryAccessError) -> Self {
+        HostError::BadMemory(err)
+    }
+}
+
+pub type HostResult<T> = Result<T, HostError>;
+
+/// Looks up 1-based handles, where 0 is never valid.
+pub fn handle_get<'a, T>(items: &'a [T], kind: &'static str, handle: u32) -> HostResult<&'a T> {
+    (handle as usize)
+        .checked_sub(1)
+        .and_then(|index| items.get(index))
+        .ok_or(HostError::BadHandle { kind, handle })
+}
@@ -1,8 +1,12 @@
-use std::{io::Cursor, num::NonZeroU64};
+use std::{
+    io::{self, Cursor},
+    num::NonZeroU64,
+    panic::catch_unwind,
+};
 
 use bytemuck::PodCastError;
-use image::{DynamicImage, ImageError, ImageReader};
-use kira::sound::static_sound::StaticSoundData;
+use image::{DynamicImage, ImageError, ImageReader, RgbaImage};
+use kira::sound::FromFileError;
 use naga::{
     front::spv,
     valid::{Capabilities, ValidationFlags, Validator},
@@ -18,15 +22,20 @@ use wgpu::{
 
 use crate::{
     app::{Buffer, System},
-    display::{MaybeGraphics, UserEvent},
+    display::{create_depth_texture, Graphics, MaybeGraphics, UserEvent},
+    error::{handle_get, HostError, HostResult},
+    pool::Pool,
+    sound::SoundSource,
 };
 
 #[derive(Debug)]
 pub struct Bindings {
-    pub pipeline: usize,
+    pub pipeline: u32,
     pub bind_group: wgpu::BindGroup,
     // TODO buffers
     pub group_index: u32,
+    /// Texture handles sampled, to keep passes from sampling their own target.
+    pub textures: Vec<u32>,
     pub updated_this_frame: bool, // TODO Track by buffer per queue instead?
 }
 
@@ -75,6 +84,38 @@ pub struct ExternMeshBuffers {
     pub index_buffer: u32,
 }
 
+/// Zero values mean straight alpha blending.
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct ExternBlendInfo {
+    /// Preset from blend_state_from_extern, where 5 uses the components.
+    pub preset: u32,
+    pub color: ExternBlendComponent,
+    pub alpha: ExternBlendComponent,
+}
+
+/// Factors and operations count in the same order as in wgpu, through
+/// SrcAlphaSaturated for factors.
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct ExternBlendComponent {
+    pub src_factor: u32,
+    pub dst_factor: u32,
+    pub operation: u32,
+}
+
+/// Layout for taca_pipeline_new, kept for apps built before blend options.
+#[derive(Clone, Copy, Debug, ValueType)]
+#[repr(C)]
+pub struct ExternPipelineBaseInfo {
+    pub depth_test: bool,
+    pub fragment: ExternPipelineShaderInfo,
+    pub vertex: ExternPipelineShaderInfo,
+    pub vertex_attributes: Span,
+    pub vertex_buffers: Span,
+}
+
+/// Layout for taca_pipeline_new2, extending the base layout.
 #[derive(Clone, Copy, Debug, ValueType)]
 #[repr(C)]
 pub struct ExternPipelineInfo {
@@ -83,6 +124,20 @@ pub struct ExternPipelineInfo {
     pub vertex: ExternPipelineShaderInfo,
     pub vertex_attributes: Span,
     pub vertex_buffers: Span,
+    pub blend: ExternBlendInfo,
+}
+
+impl From<ExternPipelineBaseInfo> for ExternPipelineInfo {
+    fn from(info: ExternPipelineBaseInfo) -> Self {
+        ExternPipelineInfo {
+            depth_test: info.depth_test,
+            fragment: info.fragment,
+            vertex: info.vertex,
+            vertex_attributes: info.vertex_attributes,
+            vertex_buffers: info.vertex_buffers,
+            blend: Default::default(),
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, ValueType)]
@@ -118,6 +173,7 @@ pub struct PipelineInfo {
     pub vertex: PipelineShaderInfo,
     pub vertex_attributes: Vec<VertexAttribute>,
     pub vertex_buffers: Vec<VertexBufferInfo>,
+    pub blend: ExternBlendInfo,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -126,13 +182,31 @@ pub struct PipelineShaderInfo {
     pub shader: u32,
 }
 
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct PassInfoExtern {
+    /// Texture from taca_texture_target_new, or 0 for the window.
+    pub target: u32,
+    /// Linear rgba for clearing when color load is 1.
+    pub clear_color: [f32; 4],
+    /// Auto 0, clear 1, or load 2, and depth always clears to 1.
+    pub color_load: u32,
+    pub depth_load: u32,
+}
+
 pub struct RenderFrame {
     pub bound: bool,
     pub buffered: bool,
     pub encoder: CommandEncoder,
-    pub frame: SurfaceTexture,
+    /// None when rendering offscreen.
+    pub frame: Option<SurfaceTexture>,
     pub pass: Option<wgpu::RenderPass<'static>>,
-    pub pipeline: usize,
+    /// Handle of the applied pipeline, or 0 for none yet.
+    pub pipeline: u32,
+    /// Texture handle of the current render target, or 0 for the window.
+    pub target: u32,
+    /// Targets already drawn this frame, which later passes keep.
+    pub targets_drawn: Vec<u32>,
     pub view: TextureView,
 }
 
@@ -152,16 +226,28 @@ pub struct Span {
 #[derive(Debug)]
 pub struct Texture {
     pub data: Option<TextureData>,
+    /// Only render targets have their own depth.
+    pub depth: Option<TextureData>,
+    pub failure: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct TextureData {
     pub size: wgpu::Extent3d,
-    #[allow(unused)]
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
 }
 
+/// Pixels run in rows from the top, with none meaning all zeros.
+#[derive(Clone, Copy, Debug, ValueType)]
+#[repr(C)]
+pub struct TextureNewInfoExtern {
+    pub width: u32,
+    pub height: u32,
+    pub format: u32,
+    pub pixels: Span,
+}
+
 #[derive(Clone, Copy, Debug, Default, ValueType)]
 #[repr(C)]
 pub struct TextureInfoExtern {
@@ -193,24 +279,29 @@ pub struct VertexBufferLayout {
     attributes: Vec<wgpu::VertexAttribute>,
 }
 
-pub fn bindings_apply(system: &mut System, bindings: u32) {
-    pipelined_ensure(system);
+pub fn bindings_apply(system: &mut System, bindings: u32) -> HostResult<()> {
+    pipelined_ensure(system)?;
+    // TODO Check pipeline vs bindings.
+    let bindings = handle_get(&system.bindings, "bindings", bindings)?;
     let Some(frame) = system.frame.as_mut() else {
-        return;
+        return Ok(());
     };
+    if frame.target != 0 && bindings.textures.contains(&frame.target) {
+        let message = format!("texture {} is the pass target", frame.target);
+        return Err(HostError::BadValue(message));
+    }
     let Some(pass) = &mut frame.pass else {
-        return;
+        return Ok(());
     };
-    // TODO Check pipeline vs bindings.
-    let bindings = &system.bindings[bindings as usize - 1];
     pass.set_bind_group(bindings.group_index, &bindings.bind_group, &[]);
     frame.bound = true;
+    Ok(())
 }
 
-pub fn bindings_new(system: &mut System, bindings: BindingsInfo) {
-    pipelined_ensure(system);
-    let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        panic!();
+pub fn bindings_new(system: &mut System, bindings: BindingsInfo) -> HostResult<()> {
+    pipelined_ensure(system)?;
+    let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+        return Err(HostError::NotReady("graphics"));
     };
     let device = &gfx.device;
     if system.samplers.is_empty() {
@@ -225,13 +316,20 @@ pub fn bindings_new(system: &mut System, bindings: BindingsInfo) {
         });
         system.samplers.push(sampler);
     }
-    let pipeline_index = match bindings.pipeline {
-        0 => 0,
-        _ => bindings.pipeline as usize - 1,
+    let pipeline_handle = match bindings.pipeline {
+        0 => system.pipelines.first().unwrap_or_default(),
+        handle => handle,
+    };
+    let pipeline = system.pipelines.get(pipeline_handle)?;
+    let Some(layout_entries) = pipeline
+        .bind_group_layouts
+        .get(bindings.group_index as usize)
+    else {
+        let message = format!("no bind group {}", bindings.group_index);
+        return Err(HostError::BadValue(message));
     };
-    let pipeline = &system.pipelines[pipeline_index];
-    let layout_entries = &pipeline.bind_group_layouts[bindings.group_index as usize];
     let mut entries = vec![];
+    let mut textures = vec![];
     let mut buffer_index = 0;
     let mut sampler_index = 0;
     let mut texture_index = 0;
@@ -242,10 +340,9 @@ pub fn bindings_new(system: &mut System, bindings: BindingsInfo) {
             } => {
                 if buffer_index < bindings.buffers.len() {
                     buffer_index += 1;
-                    let buffer = &system.buffers[bindings.buffers[buffer_index - 1] as usize - 1]
-                        .gpu()
-                        .unwrap()
-                        .buffer;
+                    let buffer =
+                        &gpu_buffer_get(&system.buffers, bindings.buffers[buffer_index - 1])?
+                            .buffer;
                     entries.push(wgpu::BindGroupEntry {
                         binding: layout_entry.binding,
                         resource: buffer.as_entire_binding(),
@@ -274,32 +371,40 @@ pub fn bindings_new(system: &mut System, bindings: BindingsInfo) {
                 let sampler = match bindings.samplers.get(sampler_index) {
                     Some(sampler) => {
                         sampler_index += 1;
-                        *sampler as usize - 1
+                        *sampler
                     }
-                    None => 0,
+                    None => 1,
                 };
                 entries.push(wgpu::BindGroupEntry {
                     binding: layout_entry.binding,
-                    resource: wgpu::BindingResource::Sampler(&system.samplers[sampler]),
+                    resource: wgpu::BindingResource::Sampler(handle_get(
+                        &system.samplers,
+                        "sampler",
+                        sampler,
+                    )?),
                 });
             }
             wgpu::BindingType::Texture { .. } => {
                 let texture = match bindings.textures.get(texture_index) {
                     Some(texture) => {
                         texture_index += 1;
-                        *texture as usize - 1
+                        *texture
                     }
-                    None => 0,
+                    None => system.textures.first().unwrap_or_default(),
                 };
-                // Crash here if no texture.
+                let data = system
+                    .textures
+                    .get(texture)?
+                    .data
+                    .as_ref()
+                    .ok_or(HostError::NotReady("texture"))?;
+                textures.push(texture);
                 entries.push(wgpu::BindGroupEntry {
                     binding: layout_entry.binding,
-                    resource: wgpu::BindingResource::TextureView(
-                        &system.textures[texture].data.as_ref().unwrap().view,
-                    ),
+                    resource: wgpu::BindingResource::TextureView(&data.view),
                 });
             }
-            _ => todo!(),
+            _ => return Err(HostError::BadValue("unsupported binding type".into())),
         }
     }
     // dbg!(&entries);
@@ -312,25 +417,100 @@ pub fn bindings_new(system: &mut System, bindings: BindingsInfo) {
         label: None,
     });
     system.bindings.push(Bindings {
-        pipeline: pipeline_index + 1,
+        pipeline: pipeline_handle,
         bind_group,
         group_index: bindings.group_index,
+        textures,
         updated_this_frame: false,
     });
+    Ok(())
 }
 
-pub fn buffers_apply(system: &mut System, buffers: MeshBuffers) {
+/// Min and max ignore factors, as wgpu requires them to be One.
+fn blend_component_from_extern(
+    component: ExternBlendComponent,
+) -> HostResult<wgpu::BlendComponent> {
+    let operation = match component.operation {
+        0 => wgpu::BlendOperation::Add,
+        1 => wgpu::BlendOperation::Subtract,
+        2 => wgpu::BlendOperation::ReverseSubtract,
+        3 => wgpu::BlendOperation::Min,
+        4 => wgpu::BlendOperation::Max,
+        operation => return Err(HostError::BadValue(format!("blend operation {operation}"))),
+    };
+    let (src_factor, dst_factor) = match operation {
+        wgpu::BlendOperation::Min | wgpu::BlendOperation::Max => {
+            (wgpu::BlendFactor::One, wgpu::BlendFactor::One)
+        }
+        _ => (
+            blend_factor_from_extern(component.src_factor)?,
+            blend_factor_from_extern(component.dst_factor)?,
+        ),
+    };
+    Ok(wgpu::BlendComponent {
+        src_factor,
+        dst_factor,
+        operation,
+    })
+}
+
+fn blend_factor_from_extern(factor: u32) -> HostResult<wgpu::BlendFactor> {
+    Ok(match factor {
+        0 => wgpu::BlendFactor::Zero,
+        1 => wgpu::BlendFactor::One,
+        2 => wgpu::BlendFactor::Src,
+        3 => wgpu::BlendFactor::OneMinusSrc,
+        4 => wgpu::BlendFactor::SrcAlpha,
+        5 => wgpu::BlendFactor::OneMinusSrcAlpha,
+        6 => wgpu::BlendFactor::Dst,
+        7 => wgpu::BlendFactor::OneMinusDst,
+        8 => wgpu::BlendFactor::DstAlpha,
+        9 => wgpu::BlendFactor::OneMinusDstAlpha,
+        10 => wgpu::BlendFactor::SrcAlphaSaturated,
+        factor => return Err(HostError::BadValue(format!("blend factor {factor}"))),
+    })
+}
+
+/// Maps presets for straight alpha 0, premultiplied 1, additive 2, multiply 3,
+/// replace 4, and custom components 5, where preset alpha adds under the same
+/// terms as premultiplied.
+fn blend_state_from_extern(blend: &ExternBlendInfo) -> HostResult<Option<wgpu::BlendState>> {
+    let component = |src_factor, dst_factor| wgpu::BlendComponent {
+        src_factor,
+        dst_factor,
+        operation: wgpu::BlendOperation::Add,
+    };
+    let alpha = component(wgpu::BlendFactor::One, wgpu::BlendFactor::OneMinusSrcAlpha);
+    let color = match blend.preset {
+        0 => component(
+            wgpu::BlendFactor::SrcAlpha,
+            wgpu::BlendFactor::OneMinusSrcAlpha,
+        ),
+        1 => alpha,
+        2 => component(wgpu::BlendFactor::SrcAlpha, wgpu::BlendFactor::One),
+        3 => component(wgpu::BlendFactor::Dst, wgpu::BlendFactor::Zero),
+        4 => return Ok(None),
+        5 => {
+            return Ok(Some(wgpu::BlendState {
+                color: blend_component_from_extern(blend.color)?,
+                alpha: blend_component_from_extern(blend.alpha)?,
+            }))
+        }
+        preset => return Err(HostError::BadValue(format!("blend {preset}"))),
+    };
+    Ok(Some(wgpu::BlendState { color, alpha }))
+}
+
+pub fn buffers_apply(system: &mut System, buffers: MeshBuffers) -> HostResult<()> {
     pass_ensure(system);
     let Some(frame) = system.frame.as_mut() else {
-        return;
+        return Ok(());
     };
     let Some(pass) = &mut frame.pass else {
-        return;
+        return Ok(());
     };
     pass.set_index_buffer(
-        system.buffers[buffers.index_buffer as usize - 1]
-            .gpu()
-            .unwrap()
+        gpu_buffer_get(&system.buffers, buffers.index_buffer)?
             .buffer
             .slice(..),
         wgpu::IndexFormat::Uint16,
@@ -338,72 +518,85 @@ pub fn buffers_apply(system: &mut System, buffers: MeshBuffers) {
     for (index, buffer) in buffers.vertex_buffers.iter().enumerate() {
         pass.set_vertex_buffer(
             index as u32,
-            system.buffers[*buffer as usize - 1]
-                .gpu()
-                .unwrap()
-                .buffer
-                .slice(..),
+            gpu_buffer_get(&system.buffers, *buffer)?.buffer.slice(..),
         );
     }
     frame.buffered = true;
+    Ok(())
 }
 
-pub fn bound_ensure(system: &mut System) {
+pub fn bound_ensure(system: &mut System) -> HostResult<()> {
     pass_ensure(system);
     let Some(frame) = system.frame.as_mut() else {
-        return;
+        return Ok(());
     };
     if frame.bound || system.bindings.is_empty() {
-        return;
+        return Ok(());
     }
     // TODO Choose a bind group that's actually for this pipeline.
-    bindings_apply(system, 1);
+    bindings_apply(system, 1)
 }
 
-pub fn buffer_update(system: &mut System, buffer: u32, bytes: &[u8], offset: u32) {
-    pipelined_ensure(system);
-    let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        panic!();
+pub fn buffer_update(
+    system: &mut System,
+    buffer: u32,
+    bytes: &[u8],
+    offset: u32,
+) -> HostResult<()> {
+    pipelined_ensure(system)?;
+    let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+        return Err(HostError::NotReady("graphics"));
     };
-    let buffer = &system.buffers[buffer as usize - 1].gpu().unwrap().buffer;
+    let buffer = &gpu_buffer_get(&system.buffers, buffer)?.buffer;
+    // Check here what wgpu would otherwise panic on.
+    let end = offset as wgpu::BufferAddress + bytes.len() as wgpu::BufferAddress;
+    if end > buffer.size() {
+        let message = format!("update end {end} beyond buffer size {}", buffer.size());
+        return Err(HostError::BadValue(message));
+    }
+    let align = wgpu::COPY_BUFFER_ALIGNMENT;
+    if offset as wgpu::BufferAddress % align != 0 || bytes.len() as wgpu::BufferAddress % align != 0
+    {
+        let message = format!("update offset and size must be multiples of {align}");
+        return Err(HostError::BadValue(message));
+    }
     gfx.queue
         .write_buffer(buffer, offset as wgpu::BufferAddress, bytes);
+    Ok(())
 }
 
-pub fn buffered_ensure(system: &mut System) {
+pub fn buffered_ensure(system: &mut System) -> HostResult<()> {
     pass_ensure(system);
     let Some(frame) = system.frame.as_mut() else {
-        return;
+        return Ok(());
     };
     if frame.buffered {
-        return;
+        return Ok(());
     }
-    let index = system
-        .buffers
-        .iter()
-        .position(|it| {
-            it.gpu()
-                .map_or(false, |it| it.usage.contains(BufferUsages::INDEX))
-        })
-        .unwrap();
-    let vertex = system
-        .buffers
-        .iter()
-        .position(|it| {
-            it.gpu()
-                .map_or(false, |it| it.usage.contains(BufferUsages::VERTEX))
-        })
-        .unwrap();
+    let find = |usage: BufferUsages| {
+        system
+            .buffers
+            .iter()
+            .find(|(_, it)| it.gpu().map_or(false, |it| it.usage.contains(usage)))
+            .map(|(handle, _)| handle)
+    };
+    let index = find(BufferUsages::INDEX).ok_or(HostError::NotReady("index buffer"))?;
+    let vertex = find(BufferUsages::VERTEX).ok_or(HostError::NotReady("vertex buffer"))?;
     let bindings = MeshBuffers {
-        vertex_buffers: &[vertex as u32 + 1],
-        index_buffer: index as u32 + 1,
+        vertex_buffers: &[vertex],
+        index_buffer: index,
     };
-    buffers_apply(system, bindings);
+    buffers_apply(system, bindings)
 }
 
-pub fn create_buffer(system: &mut System, contents: Option<&[u8]>, size: u32, typ: u32) {
+pub fn create_buffer(
+    system: &mut System,
+    contents: Option<&[u8]>,
+    size: u32,
+    typ: u32,
+) -> HostResult<u32> {
     let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        panic!();
+        return Err(HostError::NotReady("graphics"));
     };
     let usage = match contents {
         Some(_) => BufferUsages::empty(),
@@ -412,7 +605,7 @@ pub fn create_buffer(system: &mut System, contents: Option<&[u8]>, size: u32, ty
         0 => BufferUsages::VERTEX,
         1 => BufferUsages::INDEX,
         2 => BufferUsages::UNIFORM,
-        _ => panic!(),
+        _ => return Err(HostError::BadValue(format!("buffer kind {typ}"))),
     };
     let buffer = match contents {
         Some(contents) => gfx.device.create_buffer_init(&BufferInitDescriptor {
@@ -431,10 +624,11 @@ pub fn create_buffer(system: &mut System, contents: Option<&[u8]>, size: u32, ty
     // dbg!(&contents);
     system
         .buffers
-        .push(Buffer::GpuBuffer(GpuBuffer { buffer, usage }));
+        .insert(Buffer::GpuBuffer(GpuBuffer { buffer, usage }))
 }
 
-pub fn create_pipeline(system: &mut System, info: PipelineInfo) {
+pub fn create_pipeline(system: &mut System, info: PipelineInfo) -> HostResult<u32> {
+    let blend = blend_state_from_extern(&info.blend)?;
     let (depth_write_enabled, depth_compare) = match info.depth_test {
         true => (true, wgpu::CompareFunction::Less),
         false => (false, wgpu::CompareFunction::Always),
@@ -445,15 +639,13 @@ pub fn create_pipeline(system: &mut System, info: PipelineInfo) {
             _ => entry,
         }
     }
-    fn choose_shader(shader: u32, other: u32) -> u32 {
-        match shader {
-            0 => match other {
-                0 => 1,
-                _ => other,
-            },
-            _ => shader,
-        }
-    }
+    let choose_shader = |shader: u32, other: u32| match shader {
+        0 => match other {
+            0 => system.shaders.first().unwrap_or_default(),
+            _ => other,
+        },
+        _ => shader,
+    };
     let fragment_entry_point = choose_entry(info.fragment.entry_point, FRAGMENT_ENTRY_DEFAULT);
     let fragment_shader = choose_shader(info.fragment.shader, info.vertex.shader);
     let vertex_entry_point = choose_entry(info.vertex.entry_point, VERTEX_ENTRY_DEFAULT);
@@ -469,15 +661,14 @@ pub fn create_pipeline(system: &mut System, info: PipelineInfo) {
         },
         ..info
     };
-    let Some(buffers) = vertex_buffer_layouts_build(system, info) else {
-        return;
-    };
+    let buffers = vertex_buffer_layouts_build(system, info)?;
     let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        return;
+        return Err(HostError::NotReady("graphics"));
     };
     let device = &gfx.device;
-    let fragment_shader = &system.shaders[fragment_shader as usize - 1];
-    let vertex_shader = &system.shaders[vertex_shader as usize - 1];
+    let fragment_shader = system.shaders.get(fragment_shader)?;
+    let vertex_shader = system.shaders.get(vertex_shader)?;
+    entry_point_find(fragment_shader, &fragment_entry_point)?;
     // TODO Option for no uniforms?
     // let min_binding_size = uniforms_binding_size_find(vertex_shader);
     // TODO Extract and use bindings, including uniforms.
@@ -524,18 +715,7 @@ pub fn create_pipeline(system: &mut System, info: PipelineInfo) {
             compilation_options: Default::default(),
             targets: &[Some(wgpu::ColorTargetState {
                 format: TextureFormat::Bgra8Unorm,
-                blend: Some(wgpu::BlendState {
-                    color: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::SrcAlpha,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
-                    alpha: wgpu::BlendComponent {
-                        src_factor: wgpu::BlendFactor::One,
-                        dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                        operation: wgpu::BlendOperation::Add,
-                    },
-                }),
+                blend,
                 write_mask: wgpu::ColorWrites::ALL,
             })],
         }),
@@ -551,12 +731,21 @@ pub fn create_pipeline(system: &mut System, info: PipelineInfo) {
         multiview: None,
         cache: None,
     });
-    system.pipelines.push(Pipeline {
+    system.pipelines.insert(Pipeline {
         bind_group_layouts,
         bind_group_index: 0,
         bind_groups: vec![],
         pipeline,
-    });
+    })
+}
+
+fn entry_point_find<'a>(shader: &'a Shader, name: &str) -> HostResult<&'a naga::EntryPoint> {
+    shader
+        .module
+        .entry_points
+        .iter()
+        .find(|it| it.name == name)
+        .ok_or_else(|| HostError::BadValue(format!("no shader entry point {name}")))
 }
 
 pub fn frame_commit(system: &mut System) {
@@ -572,29 +761,87 @@ pub fn frame_commit(system: &mut System) {
     let command_buffer = frame.encoder.finish();
     // dbg!(&command_buffer);
     gfx.queue.submit([command_buffer]);
-    frame.frame.present();
+    if let Some(surface_texture) = frame.frame {
+        surface_texture.present();
+    }
     if let Some(text) = &system.text {
         let mut text = text.lock().unwrap();
         text.renderer_index = 0;
     }
 }
 
-pub fn image_decode(
-    handle: usize,
-    bytes: Vec<u8>,
-    event_loop_proxy: &winit::event_loop::EventLoopProxy<UserEvent>,
-) {
-    let cursor = Cursor::new(bytes);
-    let image = ImageReader::new(cursor)
-        .with_guessed_format()
-        .map_err(ImageError::IoError)
-        .and_then(|reader| reader.decode());
-    event_loop_proxy
-        .send_event(UserEvent::ImageDecoded { handle, image })
-        .unwrap();
+/// Starts a frame on the current surface texture if not already begun.
+fn frame_ensure(system: &mut System) {
+    let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+        return;
+    };
+    if system.frame.is_some() {
+        return;
+    }
+    let view_descriptor = TextureViewDescriptor {
+        format: Some(TextureFormat::Bgra8Unorm),
+        ..Default::default()
+    };
+    let (frame, view) = match &gfx.surface {
+        Some(surface) => {
+            let frame = surface.get_current_texture().unwrap();
+            let view = frame.texture.create_view(&view_descriptor);
+            (Some(frame), view)
+        }
+        None => {
+            let offscreen = gfx.offscreen.as_ref().unwrap();
+            (None, offscreen.texture.create_view(&view_descriptor))
+        }
+    };
+    let encoder = gfx.device.create_command_encoder(&Default::default());
+    system.frame = Some(RenderFrame {
+        bound: false,
+        buffered: false,
+        encoder,
+        frame,
+        pass: None,
+        pipeline: 0,
+        target: 0,
+        targets_drawn: vec![],
+        view,
+    });
+}
+
+/// Auto keeps targets already drawn this frame and otherwise clears to `auto`.
+fn load_op_from_extern<V>(op: u32, drawn: bool, auto: V, clear: V) -> HostResult<wgpu::LoadOp<V>> {
+    match (op, drawn) {
+        (0, false) => Ok(wgpu::LoadOp::Clear(auto)),
+        (0, true) | (2, _) => Ok(wgpu::LoadOp::Load),
+        (1, _) => Ok(wgpu::LoadOp::Clear(clear)),
+        _ => Err(HostError::BadValue(format!("load op {op}"))),
+    }
+}
+
+fn gpu_buffer_get(buffers: &Pool<Buffer>, handle: u32) -> HostResult<&GpuBuffer> {
+    buffers.get(handle)?.gpu().ok_or(HostError::BadHandle {
+        kind: "gpu buffer",
+        handle,
+    })
 }
 
-pub fn image_to_texture(system: &mut System, handle: usize, image: DynamicImage) {
+pub fn image_decode(handle: u32, bytes: Vec<u8>) -> UserEvent {
+    let image = catch_unwind(|| {
+        ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .map_err(ImageError::IoError)
+            .and_then(|reader| reader.decode())
+    })
+    .unwrap_or_else(|_| Err(ImageError::IoError(decode_panicked())));
+    UserEvent::ImageDecoded { handle, image }
+}
+
+/// Stands in for the result of a decoder that panicked, so the task still
+/// finishes and the worker thread lives on.
+fn decode_panicked() -> io::Error {
+    io::Error::other("decoder panicked")
+}
+
+pub fn image_to_texture(system: &mut System, handle: u32, image: DynamicImage) {
     // TODO Also need the texture index!
     let size = wgpu::Extent3d {
         width: image.width(),
@@ -619,7 +866,10 @@ pub fn image_to_texture(system: &mut System, handle: usize, image: DynamicImage)
         view_formats: &[],
     });
     let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-    let texture_info = &mut system.textures[handle - 1];
+    let Ok(texture_info) = system.textures.get_mut(handle) else {
+        // Dropped while decoding.
+        return;
+    };
     assert!(texture_info.data.is_none());
     gfx.queue.write_texture(
         wgpu::ImageCopyTexture {
@@ -643,39 +893,122 @@ pub fn image_to_texture(system: &mut System, handle: usize, image: DynamicImage)
     });
 }
 
+/// Reads back the offscreen texture after the frame is committed.
+pub fn offscreen_read(gfx: &Graphics) -> Option<RgbaImage> {
+    let offscreen = gfx.offscreen.as_ref()?;
+    let wgpu::Extent3d { width, height, .. } = offscreen.size;
+    // Buffer rows need padded alignment.
+    let row_size = 4 * width;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_row_size = row_size.div_ceil(align) * align;
+    let buffer = gfx.device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: (padded_row_size * height) as wgpu::BufferAddress,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = gfx.device.create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        offscreen.texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size),
+                rows_per_image: Some(height),
+            },
+        },
+        offscreen.size,
+    );
+    gfx.queue.submit([encoder.finish()]);
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    gfx.device.poll(wgpu::Maintain::Wait);
+    let mut pixels = Vec::with_capacity((row_size * height) as usize);
+    for row in slice.get_mapped_range().chunks(padded_row_size as usize) {
+        // Swizzle from bgra.
+        for pixel in row[..row_size as usize].chunks(4) {
+            pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+        }
+    }
+    buffer.unmap();
+    RgbaImage::from_raw(width, height, pixels)
+}
+
+/// Ends any current pass and begins one into the target, with 0 for the window.
+pub fn pass_begin(system: &mut System, info: &PassInfoExtern) -> HostResult<()> {
+    let target = info.target;
+    if target != 0 && system.textures.get(target)?.depth.is_none() {
+        let message = format!("texture {target} not a render target");
+        return Err(HostError::BadValue(message));
+    }
+    let drawn = system
+        .frame
+        .as_ref()
+        .is_some_and(|frame| frame.targets_drawn.contains(&target));
+    let [r, g, b, a] = info.clear_color.map(|it| it as f64);
+    let clear = wgpu::Color { r, g, b, a };
+    let load = load_op_from_extern(info.color_load, drawn, wgpu::Color::BLACK, clear)?;
+    let depth_load = load_op_from_extern(info.depth_load, drawn, 1.0, 1.0)?;
+    frame_ensure(system);
+    let Some(frame) = system.frame.as_mut() else {
+        return Ok(());
+    };
+    frame.pass.take();
+    // New passes need pipelines and buffers applied again.
+    frame.bound = false;
+    frame.buffered = false;
+    frame.pipeline = 0;
+    frame.target = target;
+    pass_ensure_load(system, load, depth_load);
+    Ok(())
+}
+
 pub fn pass_ensure(system: &mut System) {
-    pass_ensure_load(system, wgpu::LoadOp::Clear(wgpu::Color::BLACK));
+    // Clear each target on its first pass in a frame, and keep it after that.
+    let drawn = system
+        .frame
+        .as_ref()
+        .is_some_and(|frame| frame.targets_drawn.contains(&frame.target));
+    if drawn {
+        pass_ensure_load(system, wgpu::LoadOp::Load, wgpu::LoadOp::Load);
+    } else {
+        pass_ensure_load(
+            system,
+            wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            wgpu::LoadOp::Clear(1.0),
+        );
+    }
 }
 
-pub fn pass_ensure_load(system: &mut System, load: wgpu::LoadOp<wgpu::Color>) {
-    let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
+pub fn pass_ensure_load(
+    system: &mut System,
+    load: wgpu::LoadOp<wgpu::Color>,
+    depth_load: wgpu::LoadOp<f32>,
+) {
+    frame_ensure(system);
+    let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
         return;
     };
-    if system.frame.is_none() {
-        let frame = gfx.surface.get_current_texture().unwrap();
-        let view_descriptor = TextureViewDescriptor {
-            format: Some(TextureFormat::Bgra8Unorm),
-            ..Default::default()
-        };
-        let view = frame.texture.create_view(&view_descriptor);
-        let encoder = gfx.device.create_command_encoder(&Default::default());
-        system.frame = Some(RenderFrame {
-            bound: false,
-            buffered: false,
-            encoder,
-            frame,
-            pass: None,
-            pipeline: 0,
-            view,
-        });
-    }
     let Some(frame) = system.frame.as_mut() else {
-        panic!()
+        return;
     };
     if frame.pass.is_some() {
         return;
     }
-    let view = &frame.view;
+    let target = match frame.target {
+        0 => None,
+        target => system.textures.get(target).ok(),
+    };
+    let (view, depth_view) = match target {
+        Some(Texture {
+            data: Some(data),
+            depth: Some(depth),
+            ..
+        }) => (&data.view, &depth.view),
+        // Targets dropped mid frame fall back to the window.
+        _ => (&frame.view, &gfx.depth_texture.view),
+    };
     let encoder = &mut frame.encoder;
     let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
         color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -687,9 +1020,9 @@ pub fn pass_ensure_load(system: &mut System, load: wgpu::LoadOp<wgpu::Color>) {
             },
         })],
         depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-            view: &gfx.depth_texture.view,
+            view: depth_view,
             depth_ops: Some(wgpu::Operations {
-                load: wgpu::LoadOp::Clear(1.0),
+                load: depth_load,
                 store: wgpu::StoreOp::Store,
             }),
             stencil_ops: None,
@@ -697,43 +1030,70 @@ pub fn pass_ensure_load(system: &mut System, load: wgpu::LoadOp<wgpu::Color>) {
         ..Default::default()
     });
     frame.pass = Some(pass.forget_lifetime());
-    for pipeline in &mut system.pipelines {
+    if !frame.targets_drawn.contains(&frame.target) {
+        frame.targets_drawn.push(frame.target);
+    }
+    for (_, pipeline) in system.pipelines.iter_mut() {
         pipeline.bind_group_index = 0;
     }
 }
 
-pub fn pipeline_apply(system: &mut System, pipeline: u32) {
-    pipeline_ensure(system);
-    pass_ensure(system);
-    let pipeline_ind = pipeline as usize;
-    let Some(pipeline) = system.pipelines.get(pipeline_ind - 1) else {
-        return;
+/// Size of the current pass target, which is the window by default.
+pub fn pass_size(system: &System) -> (u32, u32) {
+    let target = system.frame.as_ref().map_or(0, |frame| frame.target);
+    let data = match target {
+        0 => None,
+        target => system
+            .textures
+            .get(target)
+            .ok()
+            .and_then(|texture| texture.data.as_ref()),
     };
+    match (data, &system.display.graphics) {
+        (Some(data), _) => (data.size.width, data.size.height),
+        (None, MaybeGraphics::Graphics(gfx)) => (gfx.config.width, gfx.config.height),
+        (None, _) => (0, 0),
+    }
+}
+
+pub fn pipeline_apply(system: &mut System, pipeline: u32) -> HostResult<()> {
+    pipeline_ensure(system)?;
+    pass_ensure(system);
+    let pipeline_handle = pipeline;
+    let pipeline = system.pipelines.get(pipeline)?;
     let Some(frame) = system.frame.as_mut() else {
-        return;
+        return Ok(());
     };
     let Some(pass) = &mut frame.pass else {
-        return;
+        return Ok(());
     };
-    frame.pipeline = pipeline_ind;
+    frame.pipeline = pipeline_handle;
     pass.set_pipeline(&pipeline.pipeline);
+    Ok(())
 }
 
-fn pipeline_ensure(system: &mut System) {
-    if !system.pipelines.is_empty() {
-        return;
+fn pipeline_ensure(system: &mut System) -> HostResult<()> {
+    // Without shaders, there's nothing yet to build a default from.
+    if !system.pipelines.is_empty() || system.shaders.is_empty() {
+        return Ok(());
     }
-    create_pipeline(system, Default::default());
+    create_pipeline(system, Default::default())?;
+    Ok(())
 }
 
-pub fn pipelined_ensure(system: &mut System) {
+pub fn pipelined_ensure(system: &mut System) -> HostResult<()> {
     let needed = match system.frame.as_ref() {
         Some(frame) => frame.pipeline == 0,
         _ => true,
     };
     if needed {
-        pipeline_apply(system, 1);
+        pipeline_ensure(system)?;
+        let Some(pipeline) = system.pipelines.first() else {
+            return Ok(());
+        };
+        pipeline_apply(system, pipeline)?;
     }
+    Ok(())
 }
 
 fn shader_bindings_find(shader: &Shader) -> Vec<Vec<wgpu::BindGroupLayoutEntry>> {
@@ -818,18 +1178,20 @@ fn shader_bindings_find(shader: &Shader) -> Vec<Vec<wgpu::BindGroupLayoutEntry>>
     groups
 }
 
-pub fn shader_create(system: &mut System, bytes: &[u8]) -> Shader {
+pub fn shader_create(system: &mut System, bytes: &[u8]) -> HostResult<Shader> {
     let MaybeGraphics::Graphics(gfx) = &mut system.display.graphics else {
-        panic!();
+        return Err(HostError::NotReady("graphics"));
     };
-    let module = spv::parse_u8_slice(bytes, &Default::default()).unwrap();
+    let module = spv::parse_u8_slice(bytes, &Default::default())
+        .map_err(|err| HostError::BadValue(format!("shader parse failed: {err}")))?;
     let mut validator = Validator::new(ValidationFlags::all(), Capabilities::empty());
     let _info = validator
         .validate(&module)
-        .expect("Shader validation failed");
+        .map_err(|err| HostError::BadValue(format!("shader validation failed: {err:?}")))?;
     let mut spirv_buffer = Vec::<u32>::new();
-    let spirv: &[u32] = bytemuck::try_cast_slice(bytes).unwrap_or_else(|err| match err {
-        PodCastError::AlignmentMismatch => {
+    let spirv: &[u32] = match bytemuck::try_cast_slice(bytes) {
+        Ok(spirv) => spirv,
+        Err(PodCastError::AlignmentMismatch) => {
             // Copy into an aligned buffer if not already aligned.
             for chunk in bytes.chunks_exact(4) {
                 let word = u32::from_le_bytes(chunk.try_into().unwrap());
@@ -837,33 +1199,27 @@ pub fn shader_create(system: &mut System, bytes: &[u8]) -> Shader {
             }
             &spirv_buffer
         }
-        _ => panic!(),
-    });
+        Err(err) => return Err(HostError::BadValue(format!("shader bytes: {err}"))),
+    };
     let compiled = gfx.device.create_shader_module(ShaderModuleDescriptor {
         label: None,
         source: ShaderSource::SpirV(std::borrow::Cow::Borrowed(spirv)),
     });
-    Shader {
+    Ok(Shader {
         compiled,
         // info,
         module,
-    }
+    })
 }
 
 // TODO Separate file for sound handling?
-pub fn sound_decode(
-    handle: usize,
-    bytes: Vec<u8>,
-    event_loop_proxy: &winit::event_loop::EventLoopProxy<UserEvent>,
-) {
-    let cursor = Cursor::new(bytes);
-    let sound = StaticSoundData::from_cursor(cursor);
-    event_loop_proxy
-        .send_event(UserEvent::SoundDecoded {
-            handle,
-            sound: Box::new(sound),
-        })
-        .unwrap();
+pub fn sound_decode(handle: u32, bytes: Vec<u8>, mode: u32) -> UserEvent {
+    let sound = catch_unwind(|| SoundSource::decode(bytes, mode))
+        .unwrap_or_else(|_| Err(FromFileError::IoError(decode_panicked())));
+    UserEvent::SoundDecoded {
+        handle,
+        sound: Box::new(sound),
+    }
 }
 
 fn step_mode_translate(step: u32) -> wgpu::VertexStepMode {
@@ -873,17 +1229,168 @@ fn step_mode_translate(step: u32) -> wgpu::VertexStepMode {
     }
 }
 
+fn texture_format_from_extern(format: u32) -> HostResult<TextureFormat> {
+    match format {
+        0 => Ok(TextureFormat::Rgba8Unorm),
+        1 => Ok(TextureFormat::R8Unorm),
+        _ => Err(HostError::BadValue(format!("texture format {format}"))),
+    }
+}
+
+pub fn texture_new(
+    system: &mut System,
+    info: &TextureNewInfoExtern,
+    pixels: &[u8],
+) -> HostResult<TextureData> {
+    let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+        return Err(HostError::NotReady("graphics"));
+    };
+    let format = texture_format_from_extern(info.format)?;
+    let size = texture_size_check(gfx, info.width, info.height)?;
+    let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let data = TextureData {
+        size,
+        texture,
+        view,
+    };
+    if !pixels.is_empty() {
+        texture_write(gfx, &data, wgpu::Origin3d::ZERO, size, pixels)?;
+    }
+    Ok(data)
+}
+
+/// Checks that pixels exactly fill the size, returning bytes per row.
+fn texture_bytes_check(format: TextureFormat, size: wgpu::Extent3d, len: usize) -> HostResult<u32> {
+    let pixel_size = format.block_copy_size(None).unwrap_or(4);
+    let bytes_per_row = pixel_size * size.width;
+    let expected = bytes_per_row as usize * size.height as usize;
+    if len != expected {
+        let message = format!("texture pixel bytes {len} not {expected}");
+        return Err(HostError::BadValue(message));
+    }
+    Ok(bytes_per_row)
+}
+
+/// Checks here what wgpu would otherwise panic on.
+fn texture_size_check(gfx: &Graphics, width: u32, height: u32) -> HostResult<wgpu::Extent3d> {
+    let max = gfx.device.limits().max_texture_dimension_2d;
+    if !(1..=max).contains(&width) || !(1..=max).contains(&height) {
+        let message = format!("texture size {width}x{height}");
+        return Err(HostError::BadValue(message));
+    }
+    Ok(wgpu::Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    })
+}
+
+/// Makes a texture that passes can render into, in the window's format.
+pub fn texture_target_new(system: &mut System, width: u32, height: u32) -> HostResult<Texture> {
+    let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+        return Err(HostError::NotReady("graphics"));
+    };
+    let size = texture_size_check(gfx, width, height)?;
+    let texture = gfx.device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TextureFormat::Bgra8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    Ok(Texture {
+        data: Some(TextureData {
+            size,
+            texture,
+            view,
+        }),
+        depth: Some(create_depth_texture(&gfx.device, width, height)),
+        failure: None,
+    })
+}
+
+pub fn texture_update(
+    system: &mut System,
+    texture: u32,
+    origin: wgpu::Origin3d,
+    size: wgpu::Extent3d,
+    pixels: &[u8],
+) -> HostResult<()> {
+    let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+        return Err(HostError::NotReady("graphics"));
+    };
+    let data = system
+        .textures
+        .get(texture)?
+        .data
+        .as_ref()
+        .ok_or(HostError::NotReady("texture"))?;
+    let end = [
+        origin.x as u64 + size.width as u64,
+        origin.y as u64 + size.height as u64,
+    ];
+    if end[0] > data.size.width as u64 || end[1] > data.size.height as u64 {
+        let message = format!(
+            "update end {}x{} beyond texture size {}x{}",
+            end[0], end[1], data.size.width, data.size.height
+        );
+        return Err(HostError::BadValue(message));
+    }
+    texture_write(gfx, data, origin, size, pixels)
+}
+
+/// Writes a rectangle of tightly packed pixels after checking their count.
+fn texture_write(
+    gfx: &Graphics,
+    data: &TextureData,
+    origin: wgpu::Origin3d,
+    size: wgpu::Extent3d,
+    pixels: &[u8],
+) -> HostResult<()> {
+    let bytes_per_row = texture_bytes_check(data.texture.format(), size, pixels.len())?;
+    if pixels.is_empty() {
+        return Ok(());
+    }
+    gfx.queue.write_texture(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture: &data.texture,
+            mip_level: 0,
+            origin,
+        },
+        pixels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes_per_row),
+            rows_per_image: Some(size.height),
+        },
+        size,
+    );
+    Ok(())
+}
+
 fn vertex_buffer_layouts_build(
     system: &System,
     info: PipelineInfo,
-) -> Option<Vec<VertexBufferLayout>> {
-    let shader = system.shaders.get(info.vertex.shader as usize - 1)?;
-    let entry = shader
-        .module
-        .entry_points
-        .iter()
-        .find(|it| it.name == info.vertex.entry_point)
-        .unwrap();
+) -> HostResult<Vec<VertexBufferLayout>> {
+    let shader = system.shaders.get(info.vertex.shader)?;
+    let entry = entry_point_find(shader, &info.vertex.entry_point)?;
     let types = &shader.module.types;
     let mut layouts = vec![];
     let mut layout = VertexBufferLayout {
@@ -903,7 +1410,7 @@ fn vertex_buffer_layouts_build(
         let Some(naga::Binding::Location { location, .. }) = arg.binding else {
             continue;
         };
-        let format = vertex_format_from_naga_type(&types[arg.ty].inner);
+        let format = vertex_format_from_naga_type(&types[arg.ty].inner)?;
         // Find which buffer we're at.
         loop {
             let next_buffer_index = layouts.len() + 1;
@@ -918,7 +1425,8 @@ fn vertex_buffer_layouts_build(
             }
             if total_attrs > next_buffer_info.first_attribute {
                 // Don't allow going backward.
-                return None;
+                let message = format!("vertex buffer starts before attribute {total_attrs}");
+                return Err(HostError::BadValue(message));
             }
             // We're at the next buffer.
             layouts.push(layout);
@@ -940,39 +1448,137 @@ fn vertex_buffer_layouts_build(
         total_attrs += 1;
     }
     layouts.push(layout);
-    Some(layouts)
+    Ok(layouts)
 }
 
-fn vertex_format_from_naga_type(type_inner: &naga::TypeInner) -> VertexFormat {
-    match type_inner {
+fn vertex_format_from_naga_type(type_inner: &naga::TypeInner) -> HostResult<VertexFormat> {
+    let format = match type_inner {
         naga::TypeInner::Scalar(naga::Scalar { kind, width }) => match (kind, width) {
-            (ScalarKind::Sint, 4) => VertexFormat::Sint32,
-            (ScalarKind::Uint, 4) => VertexFormat::Uint32,
-            (ScalarKind::Float, 4) => VertexFormat::Float32,
-            _ => todo!(),
+            (ScalarKind::Sint, 4) => Some(VertexFormat::Sint32),
+            (ScalarKind::Uint, 4) => Some(VertexFormat::Uint32),
+            (ScalarKind::Float, 4) => Some(VertexFormat::Float32),
+            _ => None,
         },
         naga::TypeInner::Vector {
             size,
             scalar: naga::Scalar { kind, width },
         } => match (kind, width, size) {
-            (ScalarKind::Float, 4, VectorSize::Bi) => VertexFormat::Float32x2,
-            (ScalarKind::Float, 4, VectorSize::Tri) => VertexFormat::Float32x3,
-            (ScalarKind::Float, 4, VectorSize::Quad) => VertexFormat::Float32x4,
-            _ => todo!(),
+            (ScalarKind::Float, 4, VectorSize::Bi) => Some(VertexFormat::Float32x2),
+            (ScalarKind::Float, 4, VectorSize::Tri) => Some(VertexFormat::Float32x3),
+            (ScalarKind::Float, 4, VectorSize::Quad) => Some(VertexFormat::Float32x4),
+            _ => None,
         },
-        naga::TypeInner::Matrix { .. } => todo!(),
-        naga::TypeInner::Atomic(_) => todo!(),
-        naga::TypeInner::Pointer { .. } => todo!(),
-        naga::TypeInner::ValuePointer { .. } => todo!(),
-        naga::TypeInner::Array { .. } => todo!(),
-        naga::TypeInner::Struct { .. } => todo!(),
-        naga::TypeInner::Image { .. } => todo!(),
-        naga::TypeInner::Sampler { .. } => todo!(),
-        naga::TypeInner::AccelerationStructure => todo!(),
-        naga::TypeInner::RayQuery => todo!(),
-        naga::TypeInner::BindingArray { .. } => todo!(),
-    }
+        _ => None,
+    };
+    format.ok_or_else(|| HostError::BadValue(format!("unsupported vertex type {type_inner:?}")))
 }
 
 const FRAGMENT_ENTRY_DEFAULT: &str = "fragment_main";
 const VERTEX_ENTRY_DEFAULT: &str = "vertex_main";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size_make(width: u32, height: u32) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        }
+    }
+
+    #[test]
+    fn blend_presets_and_components() {
+        let straight = blend_state_from_extern(&Default::default()).unwrap();
+        assert_eq!(straight, Some(wgpu::BlendState::ALPHA_BLENDING));
+        let premultiplied = ExternBlendInfo {
+            preset: 1,
+            ..Default::default()
+        };
+        let premultiplied = blend_state_from_extern(&premultiplied).unwrap();
+        assert_eq!(
+            premultiplied,
+            Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING)
+        );
+        let replace = ExternBlendInfo {
+            preset: 4,
+            ..Default::default()
+        };
+        assert_eq!(blend_state_from_extern(&replace).unwrap(), None);
+        let custom = ExternBlendInfo {
+            preset: 5,
+            color: ExternBlendComponent {
+                src_factor: 8,
+                dst_factor: 3,
+                operation: 2,
+            },
+            alpha: ExternBlendComponent {
+                src_factor: 0,
+                dst_factor: 0,
+                operation: 4,
+            },
+        };
+        let custom = blend_state_from_extern(&custom).unwrap().unwrap();
+        assert_eq!(
+            custom.color,
+            wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::DstAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrc,
+                operation: wgpu::BlendOperation::ReverseSubtract,
+            }
+        );
+        // Min and max force factors to One.
+        assert_eq!(
+            custom.alpha,
+            wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Max,
+            }
+        );
+    }
+
+    #[test]
+    fn blend_bad_values_fail() {
+        let component = |src_factor, operation| ExternBlendInfo {
+            preset: 5,
+            color: ExternBlendComponent {
+                src_factor,
+                dst_factor: 0,
+                operation,
+            },
+            ..Default::default()
+        };
+        for blend in [
+            component(11, 0),
+            component(0, 5),
+            ExternBlendInfo {
+                preset: 6,
+                ..Default::default()
+            },
+        ] {
+            let result = blend_state_from_extern(&blend);
+            assert!(matches!(result, Err(HostError::BadValue(_))));
+        }
+    }
+
+    #[test]
+    fn texture_bytes_match_format() {
+        let size = size_make(3, 2);
+        let rgba = TextureFormat::Rgba8Unorm;
+        assert!(matches!(texture_bytes_check(rgba, size, 24), Ok(12)));
+        let r8 = TextureFormat::R8Unorm;
+        assert!(matches!(texture_bytes_check(r8, size, 6), Ok(3)));
+        assert!(matches!(texture_bytes_check(r8, size_make(0, 0), 0), Ok(0)));
+    }
+
+    #[test]
+    fn texture_bytes_mismatch_fails() {
+        let size = size_make(3, 2);
+        for len in [0, 6, 23, 25] {
+            let result = texture_bytes_check(TextureFormat::Rgba8Unorm, size, len);
+            assert!(matches!(result, Err(HostError::BadValue(_))));
+        }
+    }
+}
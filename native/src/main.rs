@@ -3,14 +3,23 @@ use display::DisplayOptions;
 use winit::event_loop::EventLoop;
 
 mod app;
+mod bundle;
 mod display;
+mod error;
 mod gpu;
 mod key;
+mod pool;
+mod render;
+mod replay;
 mod sound;
+mod storage;
+mod stream;
+mod task;
 mod text;
 mod wasi;
-use crate::app::App;
+use crate::app::{App, AppOptions, Halt};
 use crate::display::Display;
+use crate::replay::{Recorder, Replay};
 
 #[derive(Parser)]
 #[command(about, version, long_about = None)]
@@ -21,26 +30,111 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
+    /// List the parts of a .taca file and the functions they use
+    Inspect(InspectArgs),
+    /// Bundle an app with extensions and assets into a .taca file
+    Pack(PackArgs),
+    /// Run an app offscreen, saving frames as png files
+    Render(RenderArgs),
     Run(RunArgs),
 }
 
+#[derive(Args)]
+pub struct InspectArgs {
+    pub app: String,
+}
+
+#[derive(Args)]
+pub struct PackArgs {
+    /// App wasm file
+    pub app: String,
+    /// Directory of files to include as bundle assets
+    #[arg(long)]
+    pub assets: Option<String>,
+    /// Extension wasm files, loaded in the order given
+    #[arg(long)]
+    pub ext: Vec<String>,
+    /// Stable app id for keeping storage across app versions
+    #[arg(long)]
+    pub id: Option<String>,
+    /// Output .taca file
+    #[arg(long, short)]
+    pub output: String,
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    pub app: String,
+    /// Prefer a software adapter, such as for machines without a gpu
+    #[arg(long)]
+    pub fallback_adapter: bool,
+    #[arg(long, default_value_t = 1)]
+    pub frames: u32,
+    /// Directory for frame png files
+    #[arg(long, default_value = ".")]
+    pub out: String,
+    /// Seed for deterministic wasi random bytes
+    #[arg(long)]
+    pub seed: Option<u64>,
+    #[arg(long, num_args = 2, value_names = ["SIZE_X", "SIZE_Y"])]
+    pub size: Vec<u32>,
+    /// Args passed to the app after "--"
+    #[arg(last = true)]
+    pub args: Vec<String>,
+}
+
 #[derive(Args)]
 pub struct RunArgs {
     pub app: String,
+    /// Log input events by frame to a file
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<String>,
+    /// Feed input events by frame from a recorded file
+    #[arg(long)]
+    pub replay: Option<String>,
+    /// Seed for deterministic wasi random bytes
+    #[arg(long)]
+    pub seed: Option<u64>,
     #[arg(long, num_args = 2, value_names = ["SIZE_X", "SIZE_Y"])]
     pub size: Vec<f64>,
+    /// Args passed to the app after "--"
+    #[arg(last = true)]
+    pub args: Vec<String>,
 }
 
 fn main() {
     let cli = Cli::parse();
     match &cli.command {
+        Commands::Inspect(args) => {
+            if let Err(err) = bundle::inspect(args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Pack(args) => {
+            if let Err(err) = bundle::pack(args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Render(args) => {
+            if let Err(err) = render::render(args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
         Commands::Run(args) => {
-            run(args);
+            if let Err(err) = run(args) {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
         }
     }
 }
 
-fn run(args: &RunArgs) {
+fn run(args: &RunArgs) -> Result<(), String> {
+    let recorder = args.record.as_deref().map(Recorder::create).transpose()?;
+    let replay = args.replay.as_deref().map(Replay::load).transpose()?;
     // Setup based on: https://github.com/erer1243/wgpu-0.20-winit-0.30-web-example
     let event_loop = EventLoop::with_user_event().build().unwrap();
     let options = DisplayOptions {
@@ -49,10 +143,34 @@ fn run(args: &RunArgs) {
             _ => None,
         },
     };
-    let display = Display::new(&event_loop, options);
-    let mut app = Box::new(App::load(&args.app, display));
+    let mut display = Display::new(&event_loop, options);
+    display.recorder = recorder;
+    display.replay = replay;
+    let mut app = Box::new(App::load(
+        &args.app,
+        AppOptions {
+            args: app_args(&args.app, &args.args),
+            seed: args.seed,
+            // Keep time in step with recorded frames.
+            stepped_clock: args.record.is_some() || args.replay.is_some(),
+        },
+        display,
+    ));
     // This should be safe because we only initiate app activity from display itself.
     // TODO Ensure that all event handling is on a single thread.
     let ptr = &mut *app as *mut App;
     app.run(event_loop, ptr);
+    match app.halt {
+        Some(Halt::Exit(code)) => std::process::exit(code as i32),
+        Some(Halt::Failure(_)) => std::process::exit(1),
+        None => Ok(()),
+    }
+}
+
+/// Wasi args start with the app path.
+pub fn app_args(app: &str, args: &[String]) -> Vec<String> {
+    [app.to_string()]
+        .into_iter()
+        .chain(args.iter().cloned())
+        .collect()
 }
@@ -0,0 +1,210 @@
+use crate::error::{HostError, HostResult};
+
+const INDEX_BITS: u32 = 20;
+const INDEX_MASK: u32 = (1 << INDEX_BITS) - 1;
+const GENERATION_MAX: u32 = u32::MAX >> INDEX_BITS;
+
+/// Slots addressed by handles that pack a 1-based index in the low bits and a
+/// generation in the high bits, so handles to dropped items don't alias new
+/// ones. Handles stay 1, 2, 3, and so on until slots get reused.
+pub struct Pool<T> {
+    free: Vec<usize>,
+    kind: &'static str,
+    slots: Vec<Slot<T>>,
+}
+
+struct Slot<T> {
+    generation: u32,
+    item: Option<T>,
+}
+
+impl<T> Pool<T> {
+    pub fn new(kind: &'static str) -> Pool<T> {
+        Pool {
+            free: vec![],
+            kind,
+            slots: vec![],
+        }
+    }
+
+    /// The handle of the first live item in slot order.
+    pub fn first(&self) -> Option<u32> {
+        self.iter().next().map(|(handle, _)| handle)
+    }
+
+    pub fn get(&self, handle: u32) -> HostResult<&T> {
+        self.slot_index(handle)
+            .and_then(|index| self.slots[index].item.as_ref())
+            .ok_or_else(|| self.bad_handle(handle))
+    }
+
+    pub fn get_mut(&mut self, handle: u32) -> HostResult<&mut T> {
+        let bad_handle = self.bad_handle(handle);
+        self.slot_index(handle)
+            .and_then(|index| self.slots[index].item.as_mut())
+            .ok_or(bad_handle)
+    }
+
+    pub fn insert(&mut self, item: T) -> HostResult<u32> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            // Stay within index bits, counting from 1.
+            None if self.slots.len() >= INDEX_MASK as usize => {
+                return Err(HostError::BadValue(format!("too many {}s", self.kind)));
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    item: None,
+                });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.item = Some(item);
+        Ok(handle_make(index, slot.generation))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Iterates live items along with their handles, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let item = slot.item.as_ref()?;
+            Some((handle_make(index, slot.generation), item))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u32, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let item = slot.item.as_mut()?;
+                Some((handle_make(index, slot.generation), item))
+            })
+    }
+
+    pub fn remove(&mut self, handle: u32) -> HostResult<T> {
+        let bad_handle = self.bad_handle(handle);
+        let slot = match self.slot_index(handle) {
+            Some(index) => &mut self.slots[index],
+            None => return Err(bad_handle),
+        };
+        let item = slot.item.take().ok_or(bad_handle)?;
+        slot_free(slot, handle_index(handle), &mut self.free);
+        Ok(item)
+    }
+
+    /// Removes each item for which `keep` returns false.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if slot.item.as_ref().is_some_and(|item| !keep(item)) {
+                slot.item = None;
+                slot_free(slot, index, &mut self.free);
+            }
+        }
+    }
+
+    fn bad_handle(&self, handle: u32) -> HostError {
+        HostError::BadHandle {
+            kind: self.kind,
+            handle,
+        }
+    }
+
+    fn slot_index(&self, handle: u32) -> Option<usize> {
+        let index = handle_index(handle);
+        let slot = self.slots.get(index)?;
+        (slot.generation == handle >> INDEX_BITS).then_some(index)
+    }
+}
+
+/// Wraps around for handle 0, which then matches no slot.
+fn handle_index(handle: u32) -> usize {
+    ((handle & INDEX_MASK) as usize).wrapping_sub(1)
+}
+
+fn slot_free<T>(slot: &mut Slot<T>, index: usize, free: &mut Vec<usize>) {
+    // Retire slots that run out of generations rather than risk aliasing.
+    if slot.generation < GENERATION_MAX {
+        slot.generation += 1;
+        free.push(index);
+    }
+}
+
+fn handle_make(index: usize, generation: u32) -> u32 {
+    (generation << INDEX_BITS) | (index as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handles_count_from_one() {
+        let mut pool = Pool::new("thing");
+        assert_eq!(pool.insert('a').unwrap(), 1);
+        assert_eq!(pool.insert('b').unwrap(), 2);
+        assert_eq!(pool.first(), Some(1));
+        assert_eq!(*pool.get(2).unwrap(), 'b');
+        assert!(pool.get(0).is_err());
+        assert!(pool.get(3).is_err());
+    }
+
+    #[test]
+    fn reused_slots_get_new_generations() {
+        let mut pool = Pool::new("thing");
+        let old = pool.insert('a').unwrap();
+        assert_eq!(pool.remove(old).unwrap(), 'a');
+        let new = pool.insert('b').unwrap();
+        assert_eq!(handle_index(new), handle_index(old));
+        assert_ne!(new, old);
+        assert!(matches!(
+            pool.get(old),
+            Err(HostError::BadHandle {
+                kind: "thing",
+                handle
+            }) if handle == old
+        ));
+        assert!(pool.remove(old).is_err());
+        assert_eq!(*pool.get(new).unwrap(), 'b');
+    }
+
+    #[test]
+    fn retired_slots_stay_free() {
+        let mut pool = Pool::new("thing");
+        pool.slots.push(Slot {
+            generation: GENERATION_MAX,
+            item: Some('a'),
+        });
+        let handle = handle_make(0, GENERATION_MAX);
+        pool.remove(handle).unwrap();
+        assert!(pool.free.is_empty());
+        assert_eq!(handle_index(pool.insert('b').unwrap()), 1);
+    }
+
+    #[test]
+    fn retain_frees_slots() {
+        let mut pool = Pool::new("thing");
+        for item in 0..4 {
+            pool.insert(item).unwrap();
+        }
+        pool.retain(|item| item % 2 == 0);
+        let items: Vec<_> = pool.iter().map(|(_, item)| *item).collect();
+        assert_eq!(items, [0, 2]);
+        assert_eq!(pool.free.len(), 2);
+    }
+
+    #[test]
+    fn full_pools_fail() {
+        let mut pool = Pool::new("thing");
+        pool.slots.resize_with(INDEX_MASK as usize, || Slot {
+            generation: 0,
+            item: Some(()),
+        });
+        assert!(matches!(pool.insert(()), Err(HostError::BadValue(_))));
+    }
+}
@@ -0,0 +1,74 @@
+use std::{fs, path::Path, sync::mpsc::channel};
+
+use crate::{
+    app::{App, AppOptions, Halt},
+    app_args,
+    display::{create_graphics_headless, Display, Graphics, MaybeGraphics},
+    gpu::offscreen_read,
+    RenderArgs,
+};
+
+/// Runs the app without a window, saving each frame as a png.
+pub fn render(args: &RenderArgs) -> Result<(), String> {
+    let (width, height) = match args.size.as_slice() {
+        &[size_x, size_y] => (size_x, size_y),
+        _ => (800, 600),
+    };
+    let gfx = pollster::block_on(create_graphics_headless(
+        width,
+        height,
+        args.fallback_adapter,
+    ))?;
+    let options = AppOptions {
+        args: app_args(&args.app, &args.args),
+        seed: args.seed,
+        stepped_clock: true,
+    };
+    let mut app = App::load(&args.app, options, Display::headless(gfx));
+    let (sender, receiver) = channel();
+    app.worker_start(move |event| sender.send(event).unwrap());
+    let gfx: *const Graphics = {
+        let system = app.system.lock().unwrap();
+        let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+            return Err("graphics unavailable".into());
+        };
+        gfx as *const _
+    };
+    // Graphics lives in the display for the life of the app.
+    app.start(unsafe { &*gfx });
+    let out = Path::new(&args.out);
+    fs::create_dir_all(out).map_err(|err| format!("{}: {err}", out.display()))?;
+    for frame in 0..args.frames {
+        // Wait on pending tasks so output doesn't depend on timing.
+        loop {
+            let pending = app.system.lock().unwrap().tasks_active > 0;
+            let event = match pending {
+                true => receiver.recv().ok(),
+                false => receiver.try_recv().ok(),
+            };
+            let Some(event) = event else {
+                break;
+            };
+            app.handle(event);
+        }
+        app.listen();
+        if let Some(Halt::Exit(_)) = app.halt {
+            break;
+        }
+        let system = app.system.lock().unwrap();
+        let MaybeGraphics::Graphics(gfx) = &system.display.graphics else {
+            return Err("graphics unavailable".into());
+        };
+        let path = out.join(format!("frame-{frame:04}.png"));
+        let image = offscreen_read(gfx).ok_or("no offscreen texture to read")?;
+        image
+            .save(&path)
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    match app.halt {
+        Some(Halt::Exit(code)) => std::process::exit(code as i32),
+        // Frames still show the failure, but the exit code should also.
+        Some(Halt::Failure(_)) => std::process::exit(1),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{LineWriter, Write},
+    str::FromStr,
+};
+
+/// Input events as fed to the app, independent of winit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    Key {
+        pressed: bool,
+        key: i32,
+        text: Option<String>,
+    },
+    PointerMove {
+        x: f64,
+        y: f64,
+    },
+    Press {
+        button: u32,
+    },
+    Release {
+        button: u32,
+    },
+}
+
+/// Writes one input per line, prefixed by the count of frames drawn before it.
+pub struct Recorder {
+    writer: LineWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, String> {
+        let file = File::create(path).map_err(|err| format!("{path}: {err}"))?;
+        Ok(Recorder {
+            writer: LineWriter::new(file),
+        })
+    }
+
+    pub fn write(&mut self, frame: u64, input: &Input) {
+        writeln!(self.writer, "{}", format_line(frame, input)).unwrap();
+    }
+}
+
+pub struct Replay {
+    inputs: VecDeque<(u64, Input)>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+        let inputs = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(index, line)| {
+                parse_line(line).ok_or_else(|| format!("{path}:{}: bad replay line", index + 1))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Replay { inputs })
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Takes the next input recorded at or before the given frame.
+    pub fn next(&mut self, frame: u64) -> Option<Input> {
+        match self.inputs.front() {
+            Some((input_frame, _)) if *input_frame <= frame => {
+                self.inputs.pop_front().map(|(_, input)| input)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn format_line(frame: u64, input: &Input) -> String {
+    match input {
+        Input::Key { pressed, key, text } => {
+            let pressed = *pressed as u32;
+            match text {
+                Some(text) => format!("{frame} key {pressed} {key} {}", text_escape(text)),
+                None => format!("{frame} key {pressed} {key}"),
+            }
+        }
+        Input::PointerMove { x, y } => format!("{frame} move {x} {y}"),
+        Input::Press { button } => format!("{frame} press {button}"),
+        Input::Release { button } => format!("{frame} release {button}"),
+    }
+}
+
+fn parse_line(line: &str) -> Option<(u64, Input)> {
+    // Text comes last and might itself have spaces.
+    let mut parts = line.splitn(5, ' ');
+    let frame = parsed(&mut parts)?;
+    let kind = parts.next()?;
+    let input = match kind {
+        "key" => Input::Key {
+            pressed: parsed::<u32>(&mut parts)? != 0,
+            key: parsed(&mut parts)?,
+            text: match parts.next() {
+                Some(text) => Some(text_unescape(text)?),
+                None => None,
+            },
+        },
+        "move" => Input::PointerMove {
+            x: parsed(&mut parts)?,
+            y: parsed(&mut parts)?,
+        },
+        "press" => Input::Press {
+            button: parsed(&mut parts)?,
+        },
+        "release" => Input::Release {
+            button: parsed(&mut parts)?,
+        },
+        _ => return None,
+    };
+    Some((frame, input))
+}
+
+fn parsed<'a, T: FromStr>(parts: &mut impl Iterator<Item = &'a str>) -> Option<T> {
+    parts.next()?.parse().ok()
+}
+
+/// Keeps text to one line, since it can include line breaks.
+fn text_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn text_unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(match chars.next()? {
+                '\\' => '\\',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            }),
+            _ => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: Input) {
+        let line = format_line(12, &input);
+        assert!(!line.contains('\n'));
+        assert_eq!(parse_line(&line), Some((12, input)));
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        round_trip(Input::Key {
+            pressed: true,
+            key: 5,
+            text: None,
+        });
+        round_trip(Input::Key {
+            pressed: false,
+            key: 0,
+            text: Some("a b".into()),
+        });
+        round_trip(Input::Key {
+            pressed: true,
+            key: 0,
+            text: Some("line\nbreak\r\\n".into()),
+        });
+    }
+
+    #[test]
+    fn pointers_round_trip() {
+        round_trip(Input::PointerMove { x: 1.5, y: -20.25 });
+        round_trip(Input::Press { button: 0 });
+        round_trip(Input::Release { button: 2 });
+    }
+
+    #[test]
+    fn bad_lines_fail() {
+        assert_eq!(parse_line("1 jump 2"), None);
+        assert_eq!(parse_line("x press 0"), None);
+        assert_eq!(parse_line("1 key 1 0 bad\\q"), None);
+    }
+}
@@ -1,11 +1,182 @@
-use kira::sound::static_sound::StaticSoundData;
+use std::{io::Cursor, sync::Arc, time::Duration};
+
+use kira::{
+    effect::{
+        delay::DelayBuilder,
+        filter::{FilterBuilder, FilterMode},
+        reverb::ReverbBuilder,
+    },
+    manager::AudioManager,
+    sound::{
+        static_sound::{StaticSoundData, StaticSoundHandle, StaticSoundSettings},
+        streaming::{StreamingSoundData, StreamingSoundHandle},
+        EndPosition, FromFileError, PlaybackPosition, PlaybackRate, PlaybackState, Region,
+    },
+    track::{TrackBuilder, TrackHandle, TrackRoutes},
+    tween::Tween,
+    Volume,
+};
+use symphonia::{
+    core::{formats::FormatOptions, io::MediaSourceStream, meta::MetadataOptions, probe::Hint},
+    default::get_probe,
+};
 use wasmer::ValueType;
 
+use crate::{
+    error::{HostError, HostResult},
+    gpu::Span,
+};
+
+/// Sounds at least this long decode as they play unless apps say otherwise.
+const STREAMING_SECONDS_MIN: f64 = 30.0;
+
 #[derive(Debug)]
 pub struct Sound {
-    pub data: Option<StaticSoundData>,
+    pub data: Option<SoundSource>,
+    pub failure: Option<String>,
+    pub info: SoundInfoExtern,
+}
+
+/// Zeros for anything unknown, including everything before decode finishes.
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct SoundInfoExtern {
+    /// In seconds.
+    pub duration: f32,
+    pub sample_rate: u32,
+    pub channels: u32,
+}
+
+/// Either fully decoded or kept encoded to decode incrementally per play.
+#[derive(Debug)]
+pub enum SoundSource {
+    Static(StaticSoundData),
+    Streaming(Arc<[u8]>),
+}
+
+impl SoundSource {
+    /// Decodes for mode 0 by length, 1 as static, or 2 as streaming.
+    pub fn decode(
+        bytes: Vec<u8>,
+        mode: u32,
+    ) -> Result<(SoundSource, SoundInfoExtern), FromFileError> {
+        let bytes: Arc<[u8]> = bytes.into();
+        let mut info = sound_info_probe(bytes.clone());
+        // Probing is cheap and also checks that streaming will work.
+        let data = StreamingSoundData::from_cursor(Cursor::new(bytes.clone()))?;
+        // Unknown lengths come out as zero.
+        let duration = data.duration().as_secs_f64();
+        let source = if mode == 2 || (mode == 0 && duration >= STREAMING_SECONDS_MIN) {
+            info.duration = duration as f32;
+            SoundSource::Streaming(bytes)
+        } else {
+            let data = StaticSoundData::from_cursor(Cursor::new(bytes))?;
+            info.duration = data.duration().as_secs_f32();
+            SoundSource::Static(data)
+        };
+        Ok((source, info))
+    }
+
+    /// Plays on a fresh decoder for streaming.
+    pub fn play(
+        &self,
+        audio_manager: &mut AudioManager,
+        settings: StaticSoundSettings,
+    ) -> Result<SoundPlay, String> {
+        match self {
+            SoundSource::Static(data) => audio_manager
+                .play(data.with_settings(settings))
+                .map(SoundPlay::Static)
+                .map_err(|err| format!("{err:?}")),
+            SoundSource::Streaming(bytes) => {
+                let mut data = match StreamingSoundData::from_cursor(Cursor::new(bytes.clone())) {
+                    Ok(data) => data,
+                    Err(err) => return Err(err.to_string()),
+                };
+                data.settings.start_time = settings.start_time;
+                data.settings.loop_region = settings.loop_region;
+                data.settings.volume = settings.volume;
+                data.settings.playback_rate = settings.playback_rate;
+                data.settings.panning = settings.panning;
+                data.settings.output_destination = settings.output_destination;
+                audio_manager
+                    .play(data)
+                    .map(SoundPlay::Streaming)
+                    .map_err(|err| format!("{err:?}"))
+            }
+        }
+    }
+}
+
+pub enum SoundPlay {
+    Static(StaticSoundHandle),
+    Streaming(StreamingSoundHandle<FromFileError>),
+}
+
+impl SoundPlay {
+    pub fn pause(&mut self, tween: Tween) {
+        match self {
+            SoundPlay::Static(play) => play.pause(tween),
+            SoundPlay::Streaming(play) => play.pause(tween),
+        }
+    }
+
+    pub fn resume(&mut self, tween: Tween) {
+        match self {
+            SoundPlay::Static(play) => play.resume(tween),
+            SoundPlay::Streaming(play) => play.resume(tween),
+        }
+    }
+
+    pub fn set_panning(&mut self, panning: f64, tween: Tween) {
+        match self {
+            SoundPlay::Static(play) => play.set_panning(panning, tween),
+            SoundPlay::Streaming(play) => play.set_panning(panning, tween),
+        }
+    }
+
+    pub fn set_playback_rate(&mut self, rate: PlaybackRate, tween: Tween) {
+        match self {
+            SoundPlay::Static(play) => play.set_playback_rate(rate, tween),
+            SoundPlay::Streaming(play) => play.set_playback_rate(rate, tween),
+        }
+    }
+
+    pub fn set_volume(&mut self, volume: Volume, tween: Tween) {
+        match self {
+            SoundPlay::Static(play) => play.set_volume(volume, tween),
+            SoundPlay::Streaming(play) => play.set_volume(volume, tween),
+        }
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        match self {
+            SoundPlay::Static(play) => play.state(),
+            SoundPlay::Streaming(play) => play.state(),
+        }
+    }
+
+    pub fn stop(&mut self, tween: Tween) {
+        match self {
+            SoundPlay::Static(play) => play.stop(tween),
+            SoundPlay::Streaming(play) => play.stop(tween),
+        }
+    }
 }
 
+/// Layout for taca_sound_play, kept for apps built before looping and tracks.
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct SoundPlayBaseInfoExtern {
+    pub sound: u32,
+    pub delay: f32,
+    pub rate: f32,
+    pub rate_kind: u32,
+    pub volume: f32,
+    pub volume_kind: u32,
+}
+
+/// Layout for taca_sound_play2, extending the base layout.
 #[derive(Clone, Copy, Debug, Default, ValueType)]
 #[repr(C)]
 pub struct SoundPlayInfoExtern {
@@ -15,4 +186,268 @@ pub struct SoundPlayInfoExtern {
     pub rate_kind: u32,
     pub volume: f32,
     pub volume_kind: u32,
+    pub looping: bool,
+    /// Seconds from the start of the sound.
+    pub loop_start: f32,
+    /// Seconds from the start, or the end of the sound if not after the start.
+    pub loop_end: f32,
+    pub panning: f32,
+    /// Track 0 is the main track.
+    pub track: u32,
+}
+
+impl From<SoundPlayBaseInfoExtern> for SoundPlayInfoExtern {
+    fn from(info: SoundPlayBaseInfoExtern) -> Self {
+        SoundPlayInfoExtern {
+            sound: info.sound,
+            delay: info.delay,
+            rate: info.rate,
+            rate_kind: info.rate_kind,
+            volume: info.volume,
+            volume_kind: info.volume_kind,
+            ..Default::default()
+        }
+    }
+}
+
+/// Zero values keep kira's defaults, except that delay feedback uses NaN, and
+/// fields for other kinds are ignored.
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct SoundEffectInfoExtern {
+    pub kind: u32,
+    pub mix: f32,
+    pub filter_mode: u32,
+    pub filter_cutoff: f32,
+    pub filter_resonance: f32,
+    pub reverb_feedback: f32,
+    pub reverb_damping: f32,
+    pub delay_time: f32,
+    /// In decibels, where 0 repeats forever and NaN keeps the default of -6.
+    pub delay_feedback: f32,
+}
+
+#[derive(Clone, Copy, Debug, ValueType)]
+#[repr(C)]
+pub struct SoundTrackInfoExtern {
+    /// Track 0 is the main track.
+    pub parent: u32,
+    pub volume: f32,
+    pub volume_kind: u32,
+    pub effects: Span,
+}
+
+/// Maps app panning from -1 for left through 1 for right onto kira's 0 to 1.
+pub fn panning_from_extern(panning: f32) -> f64 {
+    ((panning.clamp(-1.0, 1.0) + 1.0) / 2.0) as f64
+}
+
+fn effect_add(track: &mut TrackBuilder, info: &SoundEffectInfoExtern) -> HostResult<()> {
+    let mix = info.mix as f64;
+    match info.kind {
+        0 => {
+            let mode = filter_mode_from_extern(info.filter_mode)?;
+            let mut builder = FilterBuilder::new().mode(mode);
+            if info.filter_cutoff != 0.0 {
+                builder = builder.cutoff(info.filter_cutoff as f64);
+            }
+            if info.filter_resonance != 0.0 {
+                builder = builder.resonance(info.filter_resonance as f64);
+            }
+            if mix != 0.0 {
+                builder = builder.mix(mix);
+            }
+            track.add_effect(builder);
+        }
+        1 => {
+            let mut builder = ReverbBuilder::new();
+            if info.reverb_feedback != 0.0 {
+                builder = builder.feedback(info.reverb_feedback as f64);
+            }
+            if info.reverb_damping != 0.0 {
+                builder = builder.damping(info.reverb_damping as f64);
+            }
+            if mix != 0.0 {
+                builder = builder.mix(mix);
+            }
+            track.add_effect(builder);
+        }
+        2 => {
+            let mut builder = DelayBuilder::new();
+            if info.delay_time != 0.0 {
+                builder = builder.delay_time(info.delay_time as f64);
+            }
+            if !info.delay_feedback.is_nan() {
+                builder = builder.feedback(Volume::Decibels(info.delay_feedback as f64));
+            }
+            if mix != 0.0 {
+                builder = builder.mix(mix);
+            }
+            track.add_effect(builder);
+        }
+        kind => return Err(HostError::BadValue(format!("sound effect kind {kind}"))),
+    }
+    Ok(())
+}
+
+fn filter_mode_from_extern(mode: u32) -> HostResult<FilterMode> {
+    Ok(match mode {
+        0 => FilterMode::LowPass,
+        1 => FilterMode::BandPass,
+        2 => FilterMode::HighPass,
+        3 => FilterMode::Notch,
+        mode => return Err(HostError::BadValue(format!("filter mode {mode}"))),
+    })
+}
+
+pub fn loop_region_from_extern(start: f32, end: f32) -> Region {
+    let start = start.max(0.0) as f64;
+    let end = end as f64;
+    Region {
+        start: PlaybackPosition::Seconds(start),
+        end: match end > start {
+            true => EndPosition::Custom(PlaybackPosition::Seconds(end)),
+            false => EndPosition::EndOfAudio,
+        },
+    }
+}
+
+pub fn rate_from_extern(rate: f32, kind: u32) -> Option<PlaybackRate> {
+    match kind {
+        0 => Some(PlaybackRate::Semitones(rate as f64)),
+        1 => Some(PlaybackRate::Factor(rate as f64)),
+        _ => None,
+    }
+}
+
+/// Builds a mixer track and its effects, routed to the main track by default.
+pub fn track_builder_from_extern(
+    info: &SoundTrackInfoExtern,
+    effects: &[SoundEffectInfoExtern],
+    parent: Option<&TrackHandle>,
+) -> HostResult<TrackBuilder> {
+    let mut builder = TrackBuilder::new();
+    if let Some(parent) = parent {
+        builder = builder.routes(TrackRoutes::parent(parent));
+    }
+    if let Some(volume) = volume_from_extern(info.volume, info.volume_kind) {
+        builder = builder.volume(volume);
+    }
+    for effect in effects {
+        effect_add(&mut builder, effect)?;
+    }
+    Ok(builder)
+}
+
+/// Reads the format from the file, since kira's decoded frames are all stereo.
+fn sound_info_probe(bytes: Arc<[u8]>) -> SoundInfoExtern {
+    let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+    let probed = get_probe().format(
+        &Hint::new(),
+        source,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    );
+    let params = probed
+        .ok()
+        .and_then(|probed| Some(probed.format.default_track()?.codec_params.clone()));
+    let Some(params) = params else {
+        return Default::default();
+    };
+    SoundInfoExtern {
+        duration: 0.0,
+        sample_rate: params.sample_rate.unwrap_or(0),
+        channels: params
+            .channels
+            .map_or(0, |channels| channels.count() as u32),
+    }
+}
+
+/// Tweens linearly over the given seconds, with bad values meaning immediate.
+pub fn tween_from_extern(duration: f32) -> Tween {
+    Tween {
+        duration: Duration::try_from_secs_f32(duration).unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+pub fn volume_from_extern(volume: f32, kind: u32) -> Option<Volume> {
+    match kind {
+        0 => Some(Volume::Decibels(volume as f64)),
+        1 => Some(Volume::Amplitude(volume as f64)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panning_clamps_to_kira_range() {
+        assert_eq!(panning_from_extern(-1.0), 0.0);
+        assert_eq!(panning_from_extern(0.0), 0.5);
+        assert_eq!(panning_from_extern(1.0), 1.0);
+        assert_eq!(panning_from_extern(-3.0), 0.0);
+        assert_eq!(panning_from_extern(3.0), 1.0);
+    }
+
+    #[test]
+    fn loop_regions_end_after_start() {
+        let region = loop_region_from_extern(1.0, 2.5);
+        assert!(matches!(region.start, PlaybackPosition::Seconds(start) if start == 1.0));
+        assert!(matches!(
+            region.end,
+            EndPosition::Custom(PlaybackPosition::Seconds(end)) if end == 2.5
+        ));
+        // Ends at or before the start mean the end of the sound.
+        let region = loop_region_from_extern(-1.0, 0.0);
+        assert!(matches!(region.start, PlaybackPosition::Seconds(start) if start == 0.0));
+        assert!(matches!(region.end, EndPosition::EndOfAudio));
+    }
+
+    #[test]
+    fn effect_modes_map_or_fail() {
+        assert!(matches!(
+            filter_mode_from_extern(0),
+            Ok(FilterMode::LowPass)
+        ));
+        assert!(matches!(
+            filter_mode_from_extern(1),
+            Ok(FilterMode::BandPass)
+        ));
+        assert!(matches!(
+            filter_mode_from_extern(2),
+            Ok(FilterMode::HighPass)
+        ));
+        assert!(matches!(filter_mode_from_extern(3), Ok(FilterMode::Notch)));
+        assert!(matches!(
+            filter_mode_from_extern(4),
+            Err(HostError::BadValue(_))
+        ));
+        let mut track = TrackBuilder::new();
+        for kind in 0..3 {
+            let info = SoundEffectInfoExtern {
+                kind,
+                ..Default::default()
+            };
+            assert!(effect_add(&mut track, &info).is_ok());
+        }
+        let info = SoundEffectInfoExtern {
+            kind: 3,
+            ..Default::default()
+        };
+        assert!(matches!(
+            effect_add(&mut track, &info),
+            Err(HostError::BadValue(_))
+        ));
+        let info = SoundEffectInfoExtern {
+            filter_mode: 4,
+            ..Default::default()
+        };
+        assert!(matches!(
+            effect_add(&mut track, &info),
+            Err(HostError::BadValue(_))
+        ));
+    }
 }
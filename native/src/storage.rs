@@ -0,0 +1,129 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Total bytes of values allowed per app.
+const STORAGE_QUOTA: u64 = 1 << 20;
+/// Hex doubles key size, and temp names add ".tmp", within the common limit
+/// of 255 bytes per file name.
+const KEY_SIZE_MAX: usize = 125;
+
+#[derive(Clone, Copy, Debug)]
+#[repr(u32)]
+pub enum StorageResult {
+    Ok = 0,
+    BadKey,
+    OverQuota,
+    Failed,
+}
+
+/// Key value storage persisted per app, one file per key.
+pub struct Storage {
+    dir: Option<PathBuf>,
+}
+
+impl Storage {
+    /// Takes the name from Bundle::storage_name.
+    pub fn new(name: &str) -> Storage {
+        let dir = data_dir().map(|data_dir| data_dir.join("taca").join("apps").join(name));
+        Storage { dir }
+    }
+
+    pub fn read(&self, key: &[u8]) -> Option<Vec<u8>> {
+        fs::read(self.key_path(key)?).ok()
+    }
+
+    /// Writes the value for the key, where empty values remove the key.
+    pub fn write(&self, key: &[u8], bytes: &[u8]) -> StorageResult {
+        let Some(path) = self.key_path(key) else {
+            return StorageResult::BadKey;
+        };
+        let result = match bytes.is_empty() {
+            true => fs::remove_file(&path).or_else(|err| match err.kind() {
+                io::ErrorKind::NotFound => Ok(()),
+                _ => Err(err),
+            }),
+            false => {
+                if self.used_except(&path) + bytes.len() as u64 > STORAGE_QUOTA {
+                    return StorageResult::OverQuota;
+                }
+                // Write then rename, so a crash doesn't leave a partial value.
+                let temp = path.with_extension("tmp");
+                fs::create_dir_all(path.parent().unwrap())
+                    .and_then(|_| fs::write(&temp, bytes))
+                    .and_then(|_| fs::rename(&temp, &path))
+            }
+        };
+        match result {
+            Ok(()) => StorageResult::Ok,
+            Err(err) => {
+                eprintln!("storage write failed: {err}");
+                StorageResult::Failed
+            }
+        }
+    }
+
+    fn key_path(&self, key: &[u8]) -> Option<PathBuf> {
+        if key.is_empty() || key.len() > KEY_SIZE_MAX {
+            return None;
+        }
+        // Hex keeps arbitrary keys safe as file names.
+        let name: String = key.iter().map(|byte| format!("{byte:02x}")).collect();
+        Some(self.dir.as_ref()?.join(name))
+    }
+
+    fn used_except(&self, path: &Path) -> u64 {
+        let Some(entries) = self.dir.as_ref().and_then(|dir| fs::read_dir(dir).ok()) else {
+            return 0;
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            // Skip the path being replaced and any temp left by a crash.
+            .filter(|entry| {
+                let entry_path = entry.path();
+                entry_path != path && entry_path.extension().is_none()
+            })
+            .filter_map(|entry| entry.metadata().ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+}
+
+fn data_dir() -> Option<PathBuf> {
+    let var = |key: &str| {
+        env::var_os(key)
+            .filter(|it| !it.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local/share")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_keys_fit_file_names() {
+        let dir = env::temp_dir().join(format!("taca-storage-{}", std::process::id()));
+        let storage = Storage {
+            dir: Some(dir.clone()),
+        };
+        let key = [0xffu8; KEY_SIZE_MAX];
+        assert!(matches!(storage.write(&key, b"hi"), StorageResult::Ok));
+        assert_eq!(storage.read(&key).unwrap(), b"hi");
+        let too_long = [0u8; KEY_SIZE_MAX + 1];
+        assert!(matches!(
+            storage.write(&too_long, b"hi"),
+            StorageResult::BadKey
+        ));
+        assert!(matches!(storage.write(&[], b"hi"), StorageResult::BadKey));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
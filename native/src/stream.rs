@@ -0,0 +1,267 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use kira::{
+    clock::clock_info::ClockInfoProvider,
+    dsp::Frame,
+    modulator::value_provider::ModulatorValueProvider,
+    sound::{Sound, SoundData},
+    OutputDestination,
+};
+use wasmer::ValueType;
+
+use crate::error::{HostError, HostResult};
+
+const SAMPLE_RATE_MAX: u32 = 384_000;
+
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct SoundStreamInfoExtern {
+    pub sample_rate: u32,
+    pub channels: u32,
+    /// Frames the ring buffer holds, with 0 meaning a quarter second.
+    pub capacity: u32,
+    /// Track 0 is the main track.
+    pub track: u32,
+}
+
+/// App side of a streaming sound, which closes the stream when dropped.
+pub struct SoundStream {
+    channels: usize,
+    ring: Arc<Mutex<StreamRing>>,
+}
+
+struct StreamRing {
+    capacity: usize,
+    closed: bool,
+    frames: VecDeque<Frame>,
+}
+
+impl SoundStream {
+    /// Returns the stream along with the sound to play for it.
+    pub fn new(
+        info: &SoundStreamInfoExtern,
+        destination: OutputDestination,
+    ) -> HostResult<(SoundStream, StreamSoundData)> {
+        if !(1..=2).contains(&info.channels) {
+            let message = format!("stream channels {}", info.channels);
+            return Err(HostError::BadValue(message));
+        }
+        if !(1..=SAMPLE_RATE_MAX).contains(&info.sample_rate) {
+            let message = format!("stream sample rate {}", info.sample_rate);
+            return Err(HostError::BadValue(message));
+        }
+        // Even the lowest rates need room for a frame.
+        let capacity = match info.capacity {
+            0 => (info.sample_rate / 4).max(1),
+            capacity => capacity,
+        } as usize;
+        // Keep bad values from eating all memory.
+        if capacity > 60 * info.sample_rate as usize {
+            let message = format!("stream capacity {capacity}");
+            return Err(HostError::BadValue(message));
+        }
+        let ring = Arc::new(Mutex::new(StreamRing {
+            capacity,
+            closed: false,
+            frames: VecDeque::with_capacity(capacity),
+        }));
+        let stream = SoundStream {
+            channels: info.channels as usize,
+            ring: ring.clone(),
+        };
+        let data = StreamSoundData {
+            destination,
+            ring,
+            sample_rate: info.sample_rate as f64,
+        };
+        Ok((stream, data))
+    }
+
+    /// Frames that can be written without any being dropped.
+    pub fn space(&self) -> usize {
+        let ring = self.ring.lock().unwrap();
+        ring.capacity - ring.frames.len()
+    }
+
+    /// Writes interleaved samples as far as they fit, returning frames written.
+    pub fn write(&self, samples: &[f32]) -> usize {
+        let mut ring = self.ring.lock().unwrap();
+        let space = ring.capacity - ring.frames.len();
+        let frames = samples.chunks_exact(self.channels).take(space);
+        let count = frames.len();
+        ring.frames.extend(frames.map(|frame| match frame {
+            [mono] => Frame::from_mono(*mono),
+            [left, right] => Frame::new(*left, *right),
+            _ => unreachable!(),
+        }));
+        count
+    }
+}
+
+impl Drop for SoundStream {
+    fn drop(&mut self) {
+        if let Ok(mut ring) = self.ring.lock() {
+            ring.closed = true;
+        }
+    }
+}
+
+pub struct StreamSoundData {
+    destination: OutputDestination,
+    ring: Arc<Mutex<StreamRing>>,
+    sample_rate: f64,
+}
+
+impl SoundData for StreamSoundData {
+    type Error = ();
+    type Handle = ();
+
+    fn into_sound(self) -> Result<(Box<dyn Sound>, Self::Handle), Self::Error> {
+        let sound = StreamSound {
+            destination: self.destination,
+            finished: false,
+            fraction: 0.0,
+            frames: [Frame::ZERO; 2],
+            ring: self.ring,
+            sample_rate: self.sample_rate,
+        };
+        Ok((Box::new(sound), ()))
+    }
+}
+
+/// Audio thread side of a stream, resampling linearly to the output rate.
+struct StreamSound {
+    destination: OutputDestination,
+    finished: bool,
+    fraction: f64,
+    /// The frames on either side of the current position.
+    frames: [Frame; 2],
+    ring: Arc<Mutex<StreamRing>>,
+    sample_rate: f64,
+}
+
+impl StreamSound {
+    fn frame_next(&mut self) -> Frame {
+        // Don't block the audio thread, and hold the last frame instead.
+        let Ok(mut ring) = self.ring.try_lock() else {
+            return self.frames[1];
+        };
+        match ring.frames.pop_front() {
+            Some(frame) => frame,
+            None => {
+                // Play out everything written before closing.
+                self.finished = ring.closed;
+                Frame::ZERO
+            }
+        }
+    }
+}
+
+impl Sound for StreamSound {
+    fn output_destination(&mut self) -> OutputDestination {
+        self.destination
+    }
+
+    fn process(
+        &mut self,
+        dt: f64,
+        _clock_info_provider: &ClockInfoProvider,
+        _modulator_value_provider: &ModulatorValueProvider,
+    ) -> Frame {
+        self.fraction += dt * self.sample_rate;
+        while self.fraction >= 1.0 {
+            self.fraction -= 1.0;
+            self.frames[0] = self.frames[1];
+            self.frames[1] = self.frame_next();
+        }
+        let [prev, next] = self.frames;
+        prev + (next - prev) * self.fraction as f32
+    }
+
+    fn finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream_make(channels: u32, capacity: u32) -> SoundStream {
+        let info = SoundStreamInfoExtern {
+            sample_rate: 8000,
+            channels,
+            capacity,
+            track: 0,
+        };
+        let (stream, _) = SoundStream::new(&info, OutputDestination::default()).unwrap();
+        stream
+    }
+
+    fn frames(stream: &SoundStream) -> Vec<(f32, f32)> {
+        let ring = stream.ring.lock().unwrap();
+        ring.frames
+            .iter()
+            .map(|frame| (frame.left, frame.right))
+            .collect()
+    }
+
+    #[test]
+    fn mono_writes_a_frame_per_sample() {
+        let stream = stream_make(1, 4);
+        assert_eq!(stream.write(&[0.25, 0.5, 0.75]), 3);
+        assert_eq!(stream.space(), 1);
+        let expected: Vec<_> = [0.25, 0.5, 0.75]
+            .map(Frame::from_mono)
+            .iter()
+            .map(|frame| (frame.left, frame.right))
+            .collect();
+        assert_eq!(frames(&stream), expected);
+    }
+
+    #[test]
+    fn stereo_drops_partial_frames() {
+        let stream = stream_make(2, 4);
+        assert_eq!(stream.write(&[0.1, 0.2, 0.3, 0.4, 0.5]), 2);
+        assert_eq!(frames(&stream), [(0.1, 0.2), (0.3, 0.4)]);
+    }
+
+    #[test]
+    fn full_rings_take_what_fits() {
+        let stream = stream_make(2, 2);
+        assert_eq!(stream.write(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6]), 2);
+        assert_eq!(stream.space(), 0);
+        assert_eq!(stream.write(&[0.7, 0.8]), 0);
+        assert_eq!(frames(&stream), [(0.1, 0.2), (0.3, 0.4)]);
+    }
+
+    #[test]
+    fn low_rates_still_hold_a_frame() {
+        let info = SoundStreamInfoExtern {
+            sample_rate: 1,
+            channels: 1,
+            capacity: 0,
+            track: 0,
+        };
+        let (stream, _) = SoundStream::new(&info, OutputDestination::default()).unwrap();
+        assert_eq!(stream.space(), 1);
+        assert_eq!(stream.write(&[0.5, 0.5]), 1);
+    }
+
+    #[test]
+    fn bad_info_fails() {
+        for (channels, sample_rate) in [(0, 8000), (3, 8000), (1, 0)] {
+            let info = SoundStreamInfoExtern {
+                sample_rate,
+                channels,
+                capacity: 0,
+                track: 0,
+            };
+            let result = SoundStream::new(&info, OutputDestination::default());
+            assert!(matches!(result, Err(HostError::BadValue(_))));
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use wasmer::ValueType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TaskKind {
+    Image = 0,
+    Sound = 1,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u32)]
+pub enum TaskStatus {
+    #[default]
+    Pending = 0,
+    Ready = 1,
+    Failed = 2,
+}
+
+impl TaskStatus {
+    /// Async results land in data, except for failures, which get a message.
+    pub fn of<T>(data: &Option<T>, failure: &Option<String>) -> TaskStatus {
+        match (data, failure) {
+            (_, Some(_)) => TaskStatus::Failed,
+            (Some(_), None) => TaskStatus::Ready,
+            (None, None) => TaskStatus::Pending,
+        }
+    }
+}
+
+/// Says which task the latest task event is about.
+#[derive(Clone, Copy, Debug, Default, ValueType)]
+#[repr(C)]
+pub struct TaskEvent {
+    pub kind: u32,
+    pub handle: u32,
+    pub status: u32,
+}
+
+impl TaskEvent {
+    pub fn new(kind: TaskKind, handle: u32, status: TaskStatus) -> TaskEvent {
+        TaskEvent {
+            kind: kind as u32,
+            handle,
+            status: status as u32,
+        }
+    }
+}
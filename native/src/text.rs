@@ -98,15 +98,10 @@ impl TextEngine {
         else {
             panic!()
         };
-        let MaybeGraphics::Graphics(Graphics {
-            ref device,
-            ref queue,
-            ref window,
-            ..
-        }) = system.display.graphics
-        else {
+        let MaybeGraphics::Graphics(ref gfx) = system.display.graphics else {
             panic!()
         };
+        let Graphics { device, queue, .. } = gfx;
         if renderer_index == renderers.len() {
             // TODO Reference from elsewhere.
             let depth = Some(wgpu::DepthStencilState {
@@ -132,7 +127,7 @@ impl TextEngine {
         buffer.set_text(font_system, text, **attrs, Shaping::Advanced);
         buffer.shape_until_scroll(font_system, false);
         // Render
-        let size = window.inner_size();
+        let size = gfx.size();
         viewport.update(
             queue,
             Resolution {
@@ -1,6 +1,11 @@
-use std::fmt;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-use wasmer::{FunctionEnvMut, ValueType, WasmPtr, WasmRef};
+use rand::RngCore;
+use wasmer::{FunctionEnvMut, MemoryAccessError, MemoryView, ValueType, WasmPtr, WasmRef};
 
 use crate::app::PartData;
 
@@ -11,7 +16,20 @@ struct WasmIOVec {
     size: u32,
 }
 
-pub fn args_get(_env: FunctionEnvMut<PartData>, _argv: u32, _argv_buf: u32) -> u32 {
+pub fn args_get(mut env: FunctionEnvMut<PartData>, argv: u32, argv_buf: u32) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let mut arg_ptr = argv_buf;
+    for (index, arg) in system.args.iter().enumerate() {
+        WasmRef::<u32>::new(&view, argv as u64 + 4 * index as u64)
+            .write(arg_ptr)
+            .unwrap();
+        // Each arg is null terminated.
+        view.write(arg_ptr as u64, arg.as_bytes()).unwrap();
+        view.write(arg_ptr as u64 + arg.len() as u64, &[0]).unwrap();
+        arg_ptr += arg.len() as u32 + 1;
+    }
     0
 }
 
@@ -20,14 +38,292 @@ pub fn args_sizes_get(
     argv_size: u32,
     argv_buf_size: u32,
 ) -> u32 {
-    let (system, store) = env.data_and_store_mut();
-    let view = system.memory.as_ref().unwrap().view(&store);
-    view.write(argv_size as u64, &[0]).unwrap();
-    view.write(argv_buf_size as u64, &[0]).unwrap();
+    let (part, store) = env.data_and_store_mut();
+    let system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let buf_size: usize = system.args.iter().map(|arg| arg.len() + 1).sum();
+    WasmRef::<u32>::new(&view, argv_size as u64)
+        .write(system.args.len() as u32)
+        .unwrap();
+    WasmRef::<u32>::new(&view, argv_buf_size as u64)
+        .write(buf_size as u32)
+        .unwrap();
     0
 }
 
-pub fn fd_close(_env: FunctionEnvMut<PartData>, _fd: u32) -> u32 {
+/// Monotonic time source, optionally stepped by frame for reproducible runs.
+pub struct Clock {
+    start: Instant,
+    stepped_nanos: Option<u64>,
+}
+
+const FRAME_NANOS: u64 = 1_000_000_000 / 60;
+
+impl Clock {
+    pub fn new(stepped: bool) -> Clock {
+        Clock {
+            start: Instant::now(),
+            stepped_nanos: stepped.then_some(0),
+        }
+    }
+
+    pub fn frame_advance(&mut self) {
+        if let Some(nanos) = &mut self.stepped_nanos {
+            *nanos += FRAME_NANOS;
+        }
+    }
+
+    pub fn now(&self) -> u64 {
+        match self.stepped_nanos {
+            Some(nanos) => nanos,
+            None => self.start.elapsed().as_nanos() as u64,
+        }
+    }
+}
+
+const ERRNO_BADF: u32 = 8;
+const ERRNO_FAULT: u32 = 21;
+const ERRNO_ILSEQ: u32 = 25;
+const ERRNO_INVAL: u32 = 28;
+const ERRNO_ISDIR: u32 = 31;
+const ERRNO_NOENT: u32 = 44;
+const ERRNO_NOTDIR: u32 = 54;
+const ERRNO_ROFS: u32 = 69;
+const ERRNO_SPIPE: u32 = 70;
+const ERRNO_NOTCAPABLE: u32 = 76;
+
+/// Fd of the preopened bundle root.
+const FD_ROOT: u32 = 3;
+
+const OFLAGS_DIRECTORY: u32 = 2;
+const OFLAGS_WRITE: u32 = 1 | 4 | 8; // creat, excl, trunc
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+/// Read, seek, tell, advise, path_open, readdir, path_filestat_get, filestat_get
+const RIGHTS_READ_ONLY: u64 =
+    (1 << 1) | (1 << 2) | (1 << 5) | (1 << 7) | (1 << 13) | (1 << 14) | (1 << 18) | (1 << 21);
+
+/// Read-only bundle entries other than wasm parts, preopened as "/".
+pub struct BundleFiles {
+    files: BTreeMap<String, Vec<u8>>,
+    fd_next: u32,
+    fds: HashMap<u32, OpenFile>,
+}
+
+enum OpenFile {
+    Dir { path: String },
+    File { path: String, pos: u64 },
+}
+
+impl BundleFiles {
+    pub fn new(files: BTreeMap<String, Vec<u8>>) -> BundleFiles {
+        let fds = HashMap::from([(FD_ROOT, OpenFile::Dir { path: "".into() })]);
+        BundleFiles {
+            files,
+            fd_next: FD_ROOT + 1,
+            fds,
+        }
+    }
+
+    /// Lists immediate entries of the dir, with whether each is a dir.
+    fn dir_entries(&self, dir: &str) -> Vec<(&str, bool)> {
+        let prefix = dir_prefix(dir);
+        let mut entries: Vec<(&str, bool)> = vec![];
+        for name in self.files.range(prefix.clone()..).map(|(name, _)| name) {
+            let Some(rest) = name.strip_prefix(&prefix) else {
+                break;
+            };
+            let entry = match rest.split_once('/') {
+                Some((dir_name, _)) => (dir_name, true),
+                None => (rest, false),
+            };
+            // Sorting keeps each dir's contents together.
+            if entries.last() != Some(&entry) {
+                entries.push(entry);
+            }
+        }
+        entries
+    }
+
+    fn dir_path(&self, fd: u32) -> Result<&str, u32> {
+        match self.fds.get(&fd) {
+            Some(OpenFile::Dir { path }) => Ok(path),
+            Some(OpenFile::File { .. }) => Err(ERRNO_NOTDIR),
+            None => Err(ERRNO_BADF),
+        }
+    }
+
+    /// Gives the file type and rights for the fd, with stdio as devices.
+    fn fdstat(&self, fd: u32) -> Result<(WasiFileType, u64), u32> {
+        match self.fds.get(&fd) {
+            Some(OpenFile::Dir { .. }) => Ok((WasiFileType::Directory, RIGHTS_READ_ONLY)),
+            Some(OpenFile::File { .. }) => Ok((WasiFileType::RegularFile, RIGHTS_READ_ONLY)),
+            None if fd <= 2 => Ok((WasiFileType::CharacterDevice, 0)),
+            None => Err(ERRNO_BADF),
+        }
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        let prefix = dir_prefix(path);
+        path.is_empty()
+            || self
+                .files
+                .range(prefix.clone()..)
+                .next()
+                .is_some_and(|(name, _)| name.starts_with(&prefix))
+    }
+
+    fn open(&mut self, dir_fd: u32, path: &str, oflags: u32) -> Result<u32, u32> {
+        let path = path_resolve(self.dir_path(dir_fd)?, path)?;
+        let file = if self.files.contains_key(&path) {
+            if oflags & OFLAGS_DIRECTORY != 0 {
+                return Err(ERRNO_NOTDIR);
+            }
+            OpenFile::File { path, pos: 0 }
+        } else if self.is_dir(&path) {
+            OpenFile::Dir { path }
+        } else {
+            return Err(ERRNO_NOENT);
+        };
+        let fd = self.fd_next;
+        self.fd_next += 1;
+        self.fds.insert(fd, file);
+        Ok(fd)
+    }
+
+    /// Reads into the given buffer, advancing the file position.
+    fn read(&mut self, fd: u32, buf: &mut [u8]) -> Result<usize, u32> {
+        match self.fds.get_mut(&fd) {
+            Some(OpenFile::File { path, pos }) => {
+                let bytes = &self.files[path.as_str()];
+                let start = (*pos as usize).min(bytes.len());
+                let count = buf.len().min(bytes.len() - start);
+                buf[..count].copy_from_slice(&bytes[start..start + count]);
+                *pos += count as u64;
+                Ok(count)
+            }
+            Some(OpenFile::Dir { .. }) => Err(ERRNO_ISDIR),
+            None => Err(ERRNO_BADF),
+        }
+    }
+
+    fn seek(&mut self, fd: u32, delta: i64, whence: u32) -> Result<u64, u32> {
+        let Some(OpenFile::File { path, pos }) = self.fds.get_mut(&fd) else {
+            return Err(ERRNO_SPIPE);
+        };
+        let base = match whence {
+            0 => 0,
+            1 => *pos as i64,
+            2 => self.files[path.as_str()].len() as i64,
+            _ => return Err(ERRNO_INVAL),
+        };
+        let new_pos = base.checked_add(delta).filter(|it| *it >= 0);
+        *pos = new_pos.ok_or(ERRNO_INVAL)? as u64;
+        Ok(*pos)
+    }
+
+    fn stat(&self, path: &str) -> Result<WasiFileStat, u32> {
+        let (file_type, size) = match self.files.get(path) {
+            Some(bytes) => (WasiFileType::RegularFile, bytes.len() as u64),
+            None if self.is_dir(path) => (WasiFileType::Directory, 0),
+            None => return Err(ERRNO_NOENT),
+        };
+        Ok(WasiFileStat {
+            dev: 0,
+            ino: 0,
+            file_type: file_type as u8,
+            _fill1: 0,
+            _fill2: 0,
+            _fill3: 0,
+            nlink: 1,
+            size,
+            atim: 0,
+            mtim: 0,
+            ctim: 0,
+        })
+    }
+}
+
+fn dir_prefix(dir: &str) -> String {
+    match dir {
+        "" => "".into(),
+        _ => format!("{dir}/"),
+    }
+}
+
+fn errno_from_access(err: MemoryAccessError) -> u32 {
+    match err {
+        MemoryAccessError::NonUtf8String => ERRNO_ILSEQ,
+        _ => ERRNO_FAULT,
+    }
+}
+
+fn iovecs_read(view: &MemoryView, iovec: u32, len: u32) -> Result<Vec<WasmIOVec>, u32> {
+    WasmPtr::<WasmIOVec>::new(iovec)
+        .slice(view, len)
+        .and_then(|slice| slice.read_to_vec())
+        .map_err(errno_from_access)
+}
+
+fn path_read(view: &MemoryView, path: u32, path_len: u32) -> Result<String, u32> {
+    WasmPtr::<u8>::new(path)
+        .read_utf8_string(view, path_len)
+        .map_err(errno_from_access)
+}
+
+/// Joins a relative path to a dir, without escaping the root.
+fn path_resolve(dir: &str, path: &str) -> Result<String, u32> {
+    let mut parts: Vec<&str> = dir.split('/').filter(|it| !it.is_empty()).collect();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop().ok_or(ERRNO_NOTCAPABLE)?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    Ok(parts.join("/"))
+}
+
+pub fn clock_res_get(mut env: FunctionEnvMut<PartData>, clock_id: u32, resolution: u32) -> u32 {
+    // Realtime, monotonic, process cpu time, or thread cpu time.
+    if clock_id > 3 {
+        return ERRNO_INVAL;
+    }
+    let (part, store) = env.data_and_store_mut();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    WasmRef::<u64>::new(&view, resolution as u64)
+        .write(1)
+        .unwrap();
+    0
+}
+
+pub fn clock_time_get(
+    mut env: FunctionEnvMut<PartData>,
+    clock_id: u32,
+    _precision: u64,
+    time: u32,
+) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let nanos = match clock_id {
+        0 => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |it| it.as_nanos() as u64),
+        // Cpu time isn't tracked separately, so treat it as monotonic.
+        1..=3 => part.system.lock().unwrap().clock.now(),
+        _ => return ERRNO_INVAL,
+    };
+    let view = part.memory.as_ref().unwrap().view(&store);
+    WasmRef::<u64>::new(&view, time as u64)
+        .write(nanos)
+        .unwrap();
+    0
+}
+
+pub fn fd_close(mut env: FunctionEnvMut<PartData>, fd: u32) -> u32 {
+    let part = env.data_mut();
+    let mut system = part.system.lock().unwrap();
+    system.files.fds.remove(&fd);
     0
 }
 
@@ -42,8 +338,33 @@ struct WasiFdStat {
     rights_inheriting: u64,
 }
 
+#[derive(Copy, Clone, Debug, ValueType)]
+#[repr(C)]
+struct WasiFileStat {
+    dev: u64,
+    ino: u64,
+    file_type: u8,
+    _fill1: u8,
+    _fill2: u16,
+    _fill3: u32,
+    nlink: u64,
+    size: u64,
+    atim: u64,
+    mtim: u64,
+    ctim: u64,
+}
+
+#[derive(Copy, Clone, Debug, ValueType)]
+#[repr(C)]
+struct WasiPrestat {
+    tag: u8,
+    _fill1: u8,
+    _fill2: u16,
+    name_len: u32,
+}
+
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 enum WasiFileType {
     Unknown = 0,
@@ -56,34 +377,182 @@ enum WasiFileType {
     SymbolicLink,
 }
 
-pub fn fd_fdstat_get(mut env: FunctionEnvMut<PartData>, _fd: u32, fdstat: u32) -> u32 {
-    let (system, store) = env.data_and_store_mut();
-    let view = system.memory.as_ref().unwrap().view(&store);
+pub fn fd_fdstat_get(mut env: FunctionEnvMut<PartData>, fd: u32, fdstat: u32) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let (file_type, rights) = match system.files.fdstat(fd) {
+        Ok(fdstat) => fdstat,
+        Err(errno) => return errno,
+    };
     let fdstat = WasmRef::<WasiFdStat>::new(&view, fdstat as u64);
     fdstat
         .write(WasiFdStat {
-            file_type: WasiFileType::CharacterDevice as u8,
+            file_type: file_type as u8,
             _fill1: 0,
             flags: 0,
             _fill2: 0,
-            rights_base: 0,
-            rights_inheriting: 0,
+            rights_base: rights,
+            rights_inheriting: rights,
         })
         .unwrap();
     0
 }
 
+pub fn fd_filestat_get(mut env: FunctionEnvMut<PartData>, fd: u32, filestat: u32) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let path = match system.files.fds.get(&fd) {
+        Some(OpenFile::Dir { path } | OpenFile::File { path, .. }) => path,
+        None => return ERRNO_BADF,
+    };
+    match system.files.stat(path) {
+        Ok(stat) => {
+            WasmRef::<WasiFileStat>::new(&view, filestat as u64)
+                .write(stat)
+                .unwrap();
+            0
+        }
+        Err(errno) => errno,
+    }
+}
+
+pub fn fd_prestat_dir_name(
+    mut env: FunctionEnvMut<PartData>,
+    fd: u32,
+    path: u32,
+    path_len: u32,
+) -> u32 {
+    if fd != FD_ROOT {
+        return ERRNO_BADF;
+    }
+    let (part, store) = env.data_and_store_mut();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    view.write(path as u64, &b"/"[..path_len.min(1) as usize])
+        .unwrap();
+    0
+}
+
+pub fn fd_prestat_get(mut env: FunctionEnvMut<PartData>, fd: u32, prestat: u32) -> u32 {
+    // Only the bundle root is preopened.
+    if fd != FD_ROOT {
+        return ERRNO_BADF;
+    }
+    let (part, store) = env.data_and_store_mut();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    WasmRef::<WasiPrestat>::new(&view, prestat as u64)
+        .write(WasiPrestat {
+            tag: 0, // dir
+            _fill1: 0,
+            _fill2: 0,
+            name_len: 1,
+        })
+        .unwrap();
+    0
+}
+
+pub fn fd_read(
+    mut env: FunctionEnvMut<PartData>,
+    fd: u32,
+    iovec: u32,
+    len: u32,
+    nread: u32,
+) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let mut system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let mut count = 0u32;
+    // Stdin is always empty.
+    if fd != 0 {
+        let ios = match iovecs_read(&view, iovec, len) {
+            Ok(ios) => ios,
+            Err(errno) => return errno,
+        };
+        for io in ios {
+            let mut buf = vec![0u8; io.size as usize];
+            let size = match system.files.read(fd, &mut buf) {
+                Ok(size) => size,
+                Err(errno) => return errno,
+            };
+            if let Err(err) = view.write(io.buf.offset() as u64, &buf[..size]) {
+                return errno_from_access(err);
+            }
+            count += size as u32;
+            if size < buf.len() {
+                break;
+            }
+        }
+    }
+    WasmRef::<u32>::new(&view, nread as u64)
+        .write(count)
+        .unwrap();
+    0
+}
+
+pub fn fd_readdir(
+    mut env: FunctionEnvMut<PartData>,
+    fd: u32,
+    buf: u32,
+    buf_len: u32,
+    cookie: u64,
+    bufused: u32,
+) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let entries = match system.files.dir_path(fd) {
+        Ok(path) => system.files.dir_entries(path),
+        Err(errno) => return errno,
+    };
+    // Each dirent is next cookie, inode, name length, and file type, then the
+    // name. Fill the whole buffer even if the last entry gets cut off, which
+    // tells the caller to retry with more space.
+    let mut bytes = vec![];
+    for (index, (name, is_dir)) in entries.iter().enumerate().skip(cookie as usize) {
+        if bytes.len() >= buf_len as usize {
+            break;
+        }
+        let file_type = match is_dir {
+            true => WasiFileType::Directory,
+            false => WasiFileType::RegularFile,
+        };
+        bytes.extend((index as u64 + 1).to_le_bytes());
+        bytes.extend(0u64.to_le_bytes());
+        bytes.extend((name.len() as u32).to_le_bytes());
+        bytes.extend([file_type as u8, 0, 0, 0]);
+        bytes.extend(name.as_bytes());
+    }
+    bytes.truncate(buf_len as usize);
+    view.write(buf as u64, &bytes).unwrap();
+    WasmRef::<u32>::new(&view, bufused as u64)
+        .write(bytes.len() as u32)
+        .unwrap();
+    0
+}
+
 pub fn fd_seek(
     mut env: FunctionEnvMut<PartData>,
-    _fd: u32,
-    _filedelta: u64,
-    _whence: u32,
+    fd: u32,
+    filedelta: u64,
+    whence: u32,
     new_offset: u32,
 ) -> u32 {
-    let (system, store) = env.data_and_store_mut();
-    let view = system.memory.as_ref().unwrap().view(&store);
-    view.write(new_offset as u64, &[0]).unwrap();
-    1
+    let (part, store) = env.data_and_store_mut();
+    let mut system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    match system.files.seek(fd, filedelta as i64, whence) {
+        Ok(pos) => {
+            WasmRef::<u64>::new(&view, new_offset as u64)
+                .write(pos)
+                .unwrap();
+            0
+        }
+        Err(errno) => {
+            view.write(new_offset as u64, &[0]).unwrap();
+            errno
+        }
+    }
 }
 
 pub fn fd_write(
@@ -93,17 +562,23 @@ pub fn fd_write(
     len: u32,
     nwritten: u32,
 ) -> u32 {
+    // Bundle files are read-only, so only stdout and stderr take writes.
+    if !matches!(fd, 1 | 2) {
+        return ERRNO_BADF;
+    }
     let (system, store) = env.data_and_store_mut();
     let view = system.memory.as_ref().unwrap().view(&store);
+    let ios = match iovecs_read(&view, iovec, len) {
+        Ok(ios) => ios,
+        Err(errno) => return errno,
+    };
     let mut count = 0u32;
-    for io in WasmPtr::<WasmIOVec>::new(iovec)
-        .slice(&view, len)
-        .unwrap()
-        .iter()
-    {
-        let io = io.read().unwrap();
+    for io in ios {
         // TODO Support arbitrary bytes to output streams? Depends on config???
-        let text = io.buf.read_utf8_string(&view, io.size).unwrap();
+        let text = match io.buf.read_utf8_string(&view, io.size) {
+            Ok(text) => text,
+            Err(err) => return errno_from_access(err),
+        };
         match fd {
             1 => print!("{}", text),
             _ => eprint!("{}", text),
@@ -116,8 +591,65 @@ pub fn fd_write(
     0
 }
 
+pub fn path_filestat_get(
+    mut env: FunctionEnvMut<PartData>,
+    fd: u32,
+    _flags: u32,
+    path: u32,
+    path_len: u32,
+    filestat: u32,
+) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let stat = path_read(&view, path, path_len)
+        .and_then(|path| path_resolve(system.files.dir_path(fd)?, &path))
+        .and_then(|path| system.files.stat(&path));
+    match stat {
+        Ok(stat) => {
+            WasmRef::<WasiFileStat>::new(&view, filestat as u64)
+                .write(stat)
+                .unwrap();
+            0
+        }
+        Err(errno) => errno,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn path_open(
+    mut env: FunctionEnvMut<PartData>,
+    fd: u32,
+    _dirflags: u32,
+    path: u32,
+    path_len: u32,
+    oflags: u32,
+    rights_base: u64,
+    _rights_inheriting: u64,
+    _fdflags: u32,
+    opened_fd: u32,
+) -> u32 {
+    if oflags & OFLAGS_WRITE != 0 || rights_base & RIGHTS_FD_WRITE != 0 {
+        return ERRNO_ROFS;
+    }
+    let (part, store) = env.data_and_store_mut();
+    let mut system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let opened =
+        path_read(&view, path, path_len).and_then(|path| system.files.open(fd, &path, oflags));
+    match opened {
+        Ok(fd) => {
+            WasmRef::<u32>::new(&view, opened_fd as u64)
+                .write(fd)
+                .unwrap();
+            0
+        }
+        Err(errno) => errno,
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
-pub struct ExitCode(u32);
+pub struct ExitCode(pub u32);
 
 impl fmt::Display for ExitCode {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -128,12 +660,63 @@ impl fmt::Display for ExitCode {
 impl std::error::Error for ExitCode {}
 
 pub fn proc_exit(code: u32) -> std::result::Result<(), ExitCode> {
-    println!("proc_exit({code})");
+    // Unwinds out of the app for the runtime to handle.
     Err(ExitCode(code))
 }
 
-pub fn random_get(_buf: u32, _buf_len: u32) -> u32 {
-    // Claim failure for now.
-    // https://github.com/ziglang/zig/blob/4d81e8ee915c3e012131cf90ed87cc8c6a01a934/stage1/wasi.c#L42
-    29
+pub fn random_get(mut env: FunctionEnvMut<PartData>, buf: u32, buf_len: u32) -> u32 {
+    let (part, store) = env.data_and_store_mut();
+    let mut system = part.system.lock().unwrap();
+    let view = part.memory.as_ref().unwrap().view(&store);
+    let mut bytes = vec![0u8; buf_len as usize];
+    system.rng.fill_bytes(&mut bytes);
+    view.write(buf as u64, &bytes).unwrap();
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmer::{Memory, MemoryType, Store};
+
+    use super::*;
+
+    fn files() -> BundleFiles {
+        BundleFiles::new(BTreeMap::from([("data/hi.txt".into(), b"hi".to_vec())]))
+    }
+
+    #[test]
+    fn unknown_fds_are_bad() {
+        let mut files = files();
+        let mut buf = [0u8; 4];
+        assert_eq!(files.fdstat(2).unwrap().0, WasiFileType::CharacterDevice);
+        assert_eq!(files.fdstat(FD_ROOT).unwrap().0, WasiFileType::Directory);
+        assert_eq!(files.fdstat(99).unwrap_err(), ERRNO_BADF);
+        assert_eq!(files.read(99, &mut buf).unwrap_err(), ERRNO_BADF);
+        assert_eq!(files.dir_path(99).unwrap_err(), ERRNO_BADF);
+        assert_eq!(files.open(99, "data", 0).unwrap_err(), ERRNO_BADF);
+        let fd = files.open(FD_ROOT, "data/hi.txt", 0).unwrap();
+        assert_eq!(files.fdstat(fd).unwrap().0, WasiFileType::RegularFile);
+        assert_eq!(files.read(fd, &mut buf).unwrap(), 2);
+    }
+
+    #[test]
+    fn bad_paths_fail() {
+        let mut files = files();
+        assert_eq!(files.open(FD_ROOT, "nope", 0).unwrap_err(), ERRNO_NOENT);
+        assert_eq!(
+            files.open(FD_ROOT, "../x", 0).unwrap_err(),
+            ERRNO_NOTCAPABLE
+        );
+        let fd = files.open(FD_ROOT, "data/hi.txt", 0).unwrap();
+        assert_eq!(files.open(fd, "x", 0).unwrap_err(), ERRNO_NOTDIR);
+        let mut store = Store::default();
+        let memory = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+        let view = memory.view(&store);
+        view.write(0, b"data\xff").unwrap();
+        assert_eq!(path_read(&view, 0, 4).unwrap(), "data");
+        assert_eq!(path_read(&view, 0, 5).unwrap_err(), ERRNO_ILSEQ);
+        let end = view.data_size() as u32;
+        assert_eq!(path_read(&view, end - 2, 4).unwrap_err(), ERRNO_FAULT);
+        assert_eq!(iovecs_read(&view, end - 4, 1).unwrap_err(), ERRNO_FAULT);
+    }
 }
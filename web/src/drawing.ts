@@ -1,14 +1,32 @@
-import { fail } from "./util";
+import { fail, failBadValue } from "./util";
 
 export type LazyTexture = Partial<Texture>;
 
 export interface Texture {
+  failure?: string;
+  /** Set only for textures made from app pixels. */
+  format?: number;
+  /** Still decoding, where other textures are ready from the start. */
+  pending?: boolean;
   // TODO Also store a baseline for all textures that for non-text is y size.
   size: [number, number];
+  /** Set only for render targets. */
+  target?: TextureTarget;
   texture: WebGLTexture;
   usedSize: [number, number];
 }
 
+/**
+ * Renders into buffers with the y axis up, for flipping into the texture at
+ * the end of each pass to match wgpu.
+ */
+export interface TextureTarget {
+  color: WebGLRenderbuffer;
+  depth: WebGLRenderbuffer;
+  framebuffer: WebGLFramebuffer;
+  resolve: WebGLFramebuffer;
+}
+
 export class TexturePipeline {
   constructor(gl: WebGL2RenderingContext) {
     this.gl = gl;
@@ -191,6 +209,161 @@ export function shaderProgramBuild(
   return program;
 }
 
+/** Internal format, format, and bytes per pixel by app texture format. */
+function textureFormatGl(gl: WebGL2RenderingContext, format: number) {
+  return (
+    [
+      [gl.RGBA8, gl.RGBA, 4],
+      [gl.R8, gl.RED, 1],
+    ][format] ?? failBadValue(`texture format ${format}`)
+  );
+}
+
+export function textureNew(
+  gl: WebGL2RenderingContext,
+  width: number,
+  height: number,
+  format: number,
+  pixels: Uint8Array
+): Texture {
+  const [internalFormat, glFormat, pixelSize] = textureFormatGl(gl, format);
+  const max = gl.getParameter(gl.MAX_TEXTURE_SIZE);
+  if (width < 1 || width > max || height < 1 || height > max) {
+    failBadValue(`texture size ${width}x${height}`);
+  }
+  if (pixels.length && pixels.length != width * height * pixelSize) {
+    failBadValue(`texture pixel bytes ${pixels.length}`);
+  }
+  const texture = gl.createTexture() ?? fail();
+  gl.bindTexture(gl.TEXTURE_2D, texture);
+  gl.pixelStorei(gl.UNPACK_ALIGNMENT, 1);
+  gl.texImage2D(
+    gl.TEXTURE_2D,
+    0,
+    internalFormat,
+    width,
+    height,
+    0,
+    glFormat,
+    gl.UNSIGNED_BYTE,
+    pixels.length ? pixels : null
+  );
+  gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_WRAP_S, gl.CLAMP_TO_EDGE);
+  gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_WRAP_T, gl.CLAMP_TO_EDGE);
+  gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MIN_FILTER, gl.NEAREST);
+  gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MAG_FILTER, gl.NEAREST);
+  return { format, size: [width, height], texture, usedSize: [width, height] };
+}
+
+export function textureTargetDrop(
+  gl: WebGL2RenderingContext,
+  target: TextureTarget
+) {
+  gl.deleteRenderbuffer(target.color);
+  gl.deleteRenderbuffer(target.depth);
+  gl.deleteFramebuffer(target.framebuffer);
+  gl.deleteFramebuffer(target.resolve);
+}
+
+export function textureTargetNew(
+  gl: WebGL2RenderingContext,
+  width: number,
+  height: number
+): Texture {
+  // Keep whatever pass is active.
+  const bound = gl.getParameter(gl.FRAMEBUFFER_BINDING);
+  const texture = textureNew(gl, width, height, 0, new Uint8Array());
+  const renderbufferMake = (format: number) => {
+    const renderbuffer = gl.createRenderbuffer() ?? fail();
+    gl.bindRenderbuffer(gl.RENDERBUFFER, renderbuffer);
+    gl.renderbufferStorage(gl.RENDERBUFFER, format, width, height);
+    return renderbuffer;
+  };
+  const color = renderbufferMake(gl.RGBA8);
+  const depth = renderbufferMake(gl.DEPTH_COMPONENT24);
+  const framebuffer = gl.createFramebuffer() ?? fail();
+  const attach = (attachment: number, renderbuffer: WebGLRenderbuffer) =>
+    gl.framebufferRenderbuffer(
+      gl.FRAMEBUFFER,
+      attachment,
+      gl.RENDERBUFFER,
+      renderbuffer
+    );
+  gl.bindFramebuffer(gl.FRAMEBUFFER, framebuffer);
+  attach(gl.COLOR_ATTACHMENT0, color);
+  attach(gl.DEPTH_ATTACHMENT, depth);
+  const resolve = gl.createFramebuffer() ?? fail();
+  gl.bindFramebuffer(gl.FRAMEBUFFER, resolve);
+  gl.framebufferTexture2D(
+    gl.FRAMEBUFFER,
+    gl.COLOR_ATTACHMENT0,
+    gl.TEXTURE_2D,
+    texture.texture,
+    0
+  );
+  gl.bindFramebuffer(gl.FRAMEBUFFER, bound);
+  texture.target = { color, depth, framebuffer, resolve };
+  return texture;
+}
+
+/** Copies the rendered image into the texture, flipping it to match wgpu. */
+export function textureTargetResolve(
+  gl: WebGL2RenderingContext,
+  texture: Texture
+) {
+  const { framebuffer, resolve } = texture.target!;
+  const [width, height] = texture.size;
+  gl.disable(gl.SCISSOR_TEST);
+  gl.bindFramebuffer(gl.READ_FRAMEBUFFER, framebuffer);
+  gl.bindFramebuffer(gl.DRAW_FRAMEBUFFER, resolve);
+  gl.blitFramebuffer(
+    0,
+    0,
+    width,
+    height,
+    0,
+    height,
+    width,
+    0,
+    gl.COLOR_BUFFER_BIT,
+    gl.NEAREST
+  );
+}
+
+/** Replaces a rectangle of a texture with tightly packed pixels. */
+export function textureUpdate(
+  gl: WebGL2RenderingContext,
+  texture: Texture,
+  pixels: Uint8Array,
+  x: number,
+  y: number,
+  width: number,
+  height: number
+) {
+  const format = texture.format ?? 0;
+  const [, glFormat, pixelSize] = textureFormatGl(gl, format);
+  if (x + width > texture.size[0] || y + height > texture.size[1]) {
+    failBadValue(`update end beyond texture size ${texture.size}`);
+  }
+  if (pixels.length != width * height * pixelSize) {
+    failBadValue(`texture pixel bytes ${pixels.length}`);
+  }
+  if (!pixels.length) return;
+  gl.bindTexture(gl.TEXTURE_2D, texture.texture);
+  gl.pixelStorei(gl.UNPACK_ALIGNMENT, 1);
+  gl.texSubImage2D(
+    gl.TEXTURE_2D,
+    0,
+    x,
+    y,
+    width,
+    height,
+    glFormat,
+    gl.UNSIGNED_BYTE,
+    pixels
+  );
+}
+
 const textureArrayBinding = 0;
 
 const textureSourceFrag = `#version 300 es
@@ -5,7 +5,7 @@ import {
   shaderToGlsl,
   ShaderStage,
 } from "../pkg/cana";
-import { Part, textEncoder } from "./part";
+import { Part, textDecoder, textEncoder } from "./part";
 import {
   Texture,
   TexturePipeline,
@@ -13,17 +13,40 @@ import {
   imageDecode,
   shaderMunge,
   shaderProgramBuild,
+  textureNew,
+  textureTargetDrop,
+  textureTargetNew,
+  textureTargetResolve,
+  textureUpdate,
 } from "./drawing";
 import { BindGroupLayout, findBindGroups } from "./gpu";
 import { keys, keyText } from "./key";
-import { fail, getF32, getU32, getU8, setF32, setU32 } from "./util";
-import { makeWasiEnv } from "./wasi";
+import { Pool } from "./pool";
+import { AppStorage, storageNameMake } from "./storage";
+import { SoundStream } from "./stream";
+import {
+  fail,
+  failBadValue,
+  getF32,
+  getU32,
+  getU8,
+  HostError,
+  setF32,
+  setU32,
+} from "./util";
+import { BundleFiles, ExitError, makeWasiEnv, randomFillMake } from "./wasi";
 import { unzipSync } from "fflate";
 
 export interface AppConfig {
+  /** Command line args for wasi, conventionally starting with the app name. */
+  args?: string[];
   canvas: HTMLCanvasElement;
   code?: ArrayBuffer | Promise<Response>;
+  /** Identifies the app for persistent storage, by default from the bundle. */
+  name?: string;
   runtimeWasm?: Promise<Response>;
+  /** Seeds deterministic wasi random bytes in place of system entropy. */
+  seed?: number;
 }
 
 export async function runApp(config: AppConfig) {
@@ -42,9 +65,10 @@ class App {
     const canvas = (this.canvas = config.canvas);
     this.addListeners(canvas);
     this.config = config;
+    this.storage = new AppStorage(config.name ?? "");
     const gl = (this.gl = config.canvas.getContext("webgl2")!);
-    gl.enable(gl.BLEND);
-    gl.blendFunc(gl.SRC_ALPHA, gl.ONE_MINUS_SRC_ALPHA);
+    this.blendDefault = blendBuild(gl, blendInfoDefault);
+    blendApply(gl, this.blendDefault);
     this.texturePipeline = new TexturePipeline(this.gl);
     // Resize will fail if we couldn't get a context.
     this.resizeCanvas();
@@ -178,7 +202,7 @@ class App {
     for (const bindingLayout of layout.bindings) {
       switch (bindingLayout?.kind) {
         case "buffer": {
-          const buffer = buffers[bindGroup.buffers[bufferIndex] - 1];
+          const buffer = buffers.get(bindGroup.buffers[bufferIndex]);
           gl.uniformBlockBinding(
             pipeline!.program,
             bindingLayout.index,
@@ -193,7 +217,11 @@ class App {
           break;
         }
         case "sampler": {
-          const texture = textures[bindGroup.textures[textureIndex] - 1];
+          const handle = bindGroup.textures[textureIndex];
+          if (handle && handle == this.passTarget) {
+            failBadValue(`texture ${handle} is the pass target`);
+          }
+          const texture = textures.get(handle);
           gl.activeTexture(gl.TEXTURE0 + textureIndex);
           gl.bindTexture(gl.TEXTURE_2D, texture.texture);
           gl.uniform1i(bindingLayout.location, textureIndex);
@@ -225,6 +253,9 @@ class App {
     return this.bindGroups.length;
   }
 
+  /** Straight alpha, for drawing outside of app pipelines. */
+  blendDefault: Blend = null;
+
   bound = false;
 
   buffersApply(part: Part, buffersPtr: number) {
@@ -233,11 +264,11 @@ class App {
     const view = part.memoryView();
     const vertexPtr = getU32(view, buffersPtr);
     const vertexLen = getU32(view, buffersPtr + 4);
-    const index = buffers[getU32(view, buffersPtr + 8) - 1];
+    const index = buffers.get(getU32(view, buffersPtr + 8));
     // TODO Predefine bindings to avoid allocations?
     const vertex = new Array<Buffer>(vertexLen);
     for (var i = 0; i < vertexLen; i += 1) {
-      vertex[i] = buffers[getU32(view, vertexPtr + 4 * i) - 1];
+      vertex[i] = buffers.get(getU32(view, vertexPtr + 4 * i));
     }
     this.boundBuffers = { index, vertex };
     this.buffered = false;
@@ -246,6 +277,15 @@ class App {
   boundBuffers: Buffers | null = null;
   boundBuffersDefault: Buffers | null = null;
 
+  bufferDrop(buffer: number) {
+    const bufferWrapper = this.buffers.remove(buffer);
+    if (bufferWrapper.kind != "cpu") {
+      this.gl.deleteBuffer(bufferWrapper.buffer);
+    }
+    // Find defaults again in case this was one.
+    this.boundBuffersDefault = null;
+  }
+
   bufferNew(part: Part, type: number, info: number) {
     const infoBytes = part.memoryViewMake(info, 2 * 4);
     const ptr = getU32(infoBytes, 0);
@@ -257,7 +297,7 @@ class App {
       // Cpu buffer.
       const bytes = new Uint8Array(data.length);
       bytes.set(data);
-      this.buffers.push({ bytes, kind: "cpu", length: bytes.length });
+      return this.buffers.insert({ bytes, kind: "cpu", length: bytes.length });
     } else {
       // Gpu buffer.
       const { gl } = this;
@@ -270,13 +310,12 @@ class App {
         fail();
       gl.bindBuffer(target, buffer);
       gl.bufferData(target, data, usage);
-      this.buffers.push({ buffer, kind, mutable: !ptr, size });
+      return this.buffers.insert({ buffer, kind, mutable: !ptr, size });
     }
-    return this.buffers.length;
   }
 
   bufferRead(part: Part, bufferPtr: number, slice: number, offset: number) {
-    const buffer = this.buffers[bufferPtr - 1];
+    const buffer = this.buffers.get(bufferPtr);
     if (buffer.kind != "cpu") {
       // TODO Support reading some gpu buffers.
       return;
@@ -289,7 +328,7 @@ class App {
   bufferUpdate(part: Part, bufferPtr: number, slice: number, offset: number) {
     const bytes = part.readBytes(slice);
     const { buffers, gl } = this;
-    const bufferWrapper = buffers[bufferPtr - 1];
+    const bufferWrapper = buffers.get(bufferPtr);
     if (bufferWrapper.kind == "cpu") {
       // Cpu buffer.
       const length = Math.max(bufferWrapper.length, offset + bytes.length);
@@ -341,7 +380,7 @@ class App {
     }
   }
 
-  buffers: Buffer[] = [];
+  buffers = new Pool<Buffer>("buffer");
 
   #buffersBind() {
     // If at least two buffers, presumes one is data and one index.
@@ -408,7 +447,7 @@ class App {
     }
     const {
       usedSize: [sizeX, sizeY],
-    } = this.textures[this.textTexture - 1];
+    } = this.textures.get(this.textTexture);
     const [alignX, alignY] = this.textAlignVals;
     // TODO Other alignments.
     switch (alignX) {
@@ -442,7 +481,8 @@ class App {
       pipeline,
       textures,
     } = this;
-    const { size, texture, usedSize } = textures[textureIndex - 1];
+    const { size, texture, usedSize } = textures.get(textureIndex);
+    blendApply(gl, this.blendDefault);
     this.texturePipeline.draw(
       texture,
       clientWidth,
@@ -453,13 +493,23 @@ class App {
       usedSize
     );
     if (pipeline) {
+      blendApply(gl, pipeline.blend);
       gl.useProgram(pipeline.program);
     }
   }
 
+  /** Error code for taca_error_last. */
+  errorLast = 0;
+
+  errorMessage = "";
+
   frameCommit() {
+    this.#passEnd();
+    this.gl.bindFramebuffer(this.gl.FRAMEBUFFER, null);
     this.bound = this.buffered = this.passBegun = false;
     this.boundBuffers = this.pipeline = null;
+    this.passTarget = 0;
+    this.targetsDrawn.clear();
   }
 
   frameCount: number = 0;
@@ -506,8 +556,9 @@ class App {
     const text = pressed ? keyText(event) : "";
     if (text) {
       const bytes = textEncoder.encode(text);
-      if (this.textBuffer) {
-        const buffer = buffers[this.textBuffer - 1] as CpuBuffer;
+      // Also replace the buffer if the app dropped it.
+      if (buffers.has(this.textBuffer)) {
+        const buffer = buffers.get(this.textBuffer) as CpuBuffer;
         if (buffer.bytes.length < bytes.length) {
           buffer.bytes = bytes;
         } else {
@@ -515,12 +566,11 @@ class App {
         }
         buffer.length = bytes.length;
       } else {
-        buffers.push({
+        this.textBuffer = buffers.insert({
           bytes,
           kind: "cpu",
           length: bytes.length,
         });
-        this.textBuffer = buffers.length;
       }
     }
     // TODO Combine text into key event again?
@@ -531,28 +581,52 @@ class App {
     if (text) {
       const textBuffer = this.textBuffer;
       setU32(textEvent, 0, textBuffer);
-      setU32(textEvent, 4, (buffers[textBuffer - 1] as CpuBuffer).length);
+      setU32(textEvent, 4, (buffers.get(textBuffer) as CpuBuffer).length);
       this.partsUpdate(eventTypes.text);
     }
   }
 
   gl: WebGL2RenderingContext;
 
+  halted = false;
+
+  /** Reports bad app input through errorLast, returning a zero value. */
+  hostCall<T>(name: string, call: () => T) {
+    try {
+      return call();
+    } catch (error) {
+      if (!(error instanceof HostError)) {
+        throw error;
+      }
+      const message = `${name}: ${error.message}`;
+      if (message != this.errorMessage) {
+        console.error(message);
+        this.errorMessage = message;
+      }
+      this.errorLast = error.code;
+      return 0;
+    }
+  }
+
   imageDecode(part: Part, bytes: number) {
     const { gl, textures } = this;
     let pointer = 0;
     const texture = imageDecode(
       gl,
       part.readBytes(bytes),
-      () => this.taskFinish(),
+      () => {
+        texture.pending = false;
+        this.taskFinish(taskKinds.image, pointer);
+      },
       (reason) => {
-        this.taskFinish();
-        fail(reason);
+        texture.pending = false;
+        texture.failure = String(reason);
+        this.taskFinish(taskKinds.image, pointer, texture.failure);
       }
     );
+    texture.pending = true;
     this.tasksActive += 1;
-    textures.push(texture);
-    pointer = textures.length;
+    pointer = textures.insert(texture);
     return pointer;
   }
 
@@ -563,28 +637,115 @@ class App {
 
   parts: Part[] = [];
 
+  /** Runs part code, stopping all parts on any failure or exit. */
+  partCall(part: Part, action: () => void) {
+    if (this.halted) {
+      return false;
+    }
+    try {
+      action();
+      return true;
+    } catch (error) {
+      this.halted = true;
+      if (error instanceof ExitError) {
+        console.log(`${part.name}: exit ${error.code}`);
+      } else {
+        // Browser stacks include wasm frames.
+        console.error(`${part.name}:`, error);
+      }
+      return false;
+    }
+  }
+
   partsUpdate(kind: number) {
     for (const part of this.parts) {
-      part.update(kind);
+      if (!this.partCall(part, () => part.update(kind))) {
+        return;
+      }
     }
   }
 
-  passBegin() {
-    let { gl, resizeNeeded } = this;
-    if (resizeNeeded) this.resizeCanvas();
-    gl.clearColor(0, 0, 0, 1);
-    gl.clear(gl.COLOR_BUFFER_BIT | gl.DEPTH_BUFFER_BIT);
+  /** Ends any current pass and begins one into the target or the window. */
+  passBegin(info: PassInfo = passInfoDefault) {
+    const { clearColor, colorLoad, depthLoad, target } = info;
+    const { gl, targetsDrawn, textures } = this;
+    // Auto clears on each target's first pass in a frame and keeps it after.
+    const drawn = targetsDrawn.has(target);
+    const clears = (load: number) => {
+      switch (load) {
+        case loadOps.auto:
+          return !drawn;
+        case loadOps.clear:
+          return true;
+        case loadOps.load:
+          return false;
+        default:
+          failBadValue(`load op ${load}`);
+      }
+    };
+    const colorClears = clears(colorLoad);
+    const depthClears = clears(depthLoad);
+    const framebuffer = target
+      ? (textures.get(target).target ??
+          failBadValue(`texture ${target} not a render target`))
+          .framebuffer
+      : null;
+    this.#passEnd();
+    // New passes need pipelines and buffers applied again.
+    this.bound = this.buffered = false;
+    this.boundBuffers = this.pipeline = null;
+    this.passTarget = target;
+    gl.disable(gl.SCISSOR_TEST);
+    gl.bindFramebuffer(gl.FRAMEBUFFER, framebuffer);
+    if (!target && this.resizeNeeded) {
+      this.resizeCanvas();
+    } else {
+      const [width, height] = this.#passSize();
+      gl.viewport(0, 0, width, height);
+      this.tacaBufferUpdate();
+    }
+    if (colorClears) {
+      const [r, g, b, a] =
+        colorLoad == loadOps.clear ? clearColor : [0, 0, 0, 1];
+      gl.clearColor(r, g, b, a);
+    }
+    gl.clearDepth(1);
+    gl.clear(
+      (colorClears ? gl.COLOR_BUFFER_BIT : 0) |
+        (depthClears ? gl.DEPTH_BUFFER_BIT : 0)
+    );
+    targetsDrawn.add(target);
     this.passBegun = true;
   }
 
   passBegun = false;
 
+  #passEnd() {
+    const { passTarget, textures } = this;
+    // Skip targets dropped during the pass.
+    if (passTarget && textures.has(passTarget)) {
+      textureTargetResolve(this.gl, textures.get(passTarget));
+    }
+  }
+
+  #passSize(): [number, number] {
+    const { canvas } = this.config;
+    const { passTarget, textures } = this;
+    return passTarget && textures.has(passTarget)
+      ? textures.get(passTarget).size
+      : [canvas.width, canvas.height];
+  }
+
+  /** Texture handle of the current render target, or 0 for the window. */
+  passTarget = 0;
+
   pipeline: Pipeline | null = null;
 
   pipelineApply(pipelinePtr: number) {
     let { gl, pipelines } = this;
-    const pipeline = (this.pipeline = pipelines[pipelinePtr - 1] ?? fail());
+    const pipeline = (this.pipeline = pipelines.get(pipelinePtr));
     (pipeline.depthTest ? gl.enable : gl.disable).call(gl, gl.DEPTH_TEST);
+    blendApply(gl, pipeline.blend);
     gl.useProgram(pipeline.program);
     this.tacaBufferEnsure();
     // Presume we need new buffers bound when the program changes.
@@ -595,7 +756,7 @@ class App {
     // console.log(pipelineInfo);
     const { gl, pipelines, shaders } = this;
     const shaderMake = (info: ShaderInfo, stage: ShaderStage) =>
-      shaderToGlsl(shaders[info.shader - 1], stage, info.entry);
+      shaderToGlsl(shaders.get(info.shader), stage, info.entry);
     const vertex = shaderMunge(
       shaderMake(pipelineInfo.vertex, ShaderStage.Vertex)
     );
@@ -608,9 +769,10 @@ class App {
     const bindGroups = findBindGroups(gl, program);
     pipelineInfo = this.#attributesBuild(program, pipelineInfo);
     const uniforms = this.#uniformsBuild(program);
-    pipelines.push({
+    const handle = pipelines.insert({
       attributes: pipelineInfo.vertexAttrs,
       bindGroups,
+      blend: blendBuild(gl, pipelineInfo.blend),
       buffers: pipelineInfo.vertexBuffers,
       depthTest: pipelineInfo.depthTest,
       program,
@@ -618,12 +780,16 @@ class App {
     });
     // console.log(pipelineInfo);
     // console.log(this.pipelines);
-    return pipelineInfo;
+    return handle;
+  }
+
+  pipelineDrop(pipeline: number) {
+    this.gl.deleteProgram(this.pipelines.remove(pipeline).program);
   }
 
   #pipelineEnsure() {
-    if (!this.pipelines.length) {
-      this.#pipelineBuild(pipelineInfoDefault());
+    if (!this.pipelines.first()) {
+      this.#pipelineBuild(pipelineInfoDefault({}, this.shaders.first()));
     }
   }
 
@@ -631,19 +797,28 @@ class App {
     if (!this.pipeline) {
       this.#pipelineEnsure();
       if (!this.passBegun) this.passBegin();
-      if (this.pipelines.length > 0) this.pipelineApply(1);
+      const pipeline = this.pipelines.first();
+      if (pipeline) this.pipelineApply(pipeline);
     }
   }
 
-  pipelineNew(part: Part, info: number) {
-    let pipelineInfo = this.pipelineInfoRead(part, info);
-    pipelineInfo = this.#pipelineBuild(pipelineInfo);
-    return this.pipelines.length;
+  pipelineNew(part: Part, info: number, infoSize: number) {
+    const pipelineInfo = this.pipelineInfoRead(part, info, infoSize);
+    return this.#pipelineBuild(pipelineInfo);
   }
 
-  private pipelineInfoRead(part: Part, info: number): PipelineInfo {
+  private pipelineInfoRead(
+    part: Part,
+    info: number,
+    infoSize: number
+  ): PipelineInfo {
     // TODO Can wit-bindgen or flatbuffers automate some of this?
-    const infoView = part.memoryViewMake(info, 11 * 4);
+    const infoView = part.memoryViewMake(info, infoSize);
+    const readBlendComponent = (offset: number): BlendComponentInfo => [
+      getU32(infoView, offset),
+      getU32(infoView, offset + 1 * 4),
+      getU32(infoView, offset + 2 * 4),
+    ];
     const readShaderInfo = (offset: number) => {
       return {
         entry: part.readString(infoView.byteOffset + offset),
@@ -651,6 +826,15 @@ class App {
       };
     };
     const pipelineInfo: PipelineInfo = {
+      // Older layouts end before blend and get the default.
+      blend:
+        infoSize > 11 * 4
+          ? {
+              alpha: readBlendComponent(15 * 4),
+              color: readBlendComponent(12 * 4),
+              preset: getU32(infoView, 11 * 4),
+            }
+          : blendInfoDefault,
       depthTest: !!getU8(infoView, 0),
       fragment: readShaderInfo(1 * 4),
       vertex: readShaderInfo(4 * 4),
@@ -675,10 +859,10 @@ class App {
         })
       ),
     };
-    return pipelineInfoDefault(pipelineInfo);
+    return pipelineInfoDefault(pipelineInfo, this.shaders.first());
   }
 
-  pipelines: Pipeline[] = [];
+  pipelines = new Pool<Pipeline>("pipeline");
 
   pointerPos: [x: number, y: number] = [0, 0];
   pointerPress = 0;
@@ -694,39 +878,78 @@ class App {
 
   resizeNeeded = false;
 
-  shaders: Shader[] = [];
-  sounds: Sound[] = [];
+  shaders = new Pool<Shader>("shader");
+  sounds = new Pool<Sound>("sound");
 
   soundDecode(part: Part, bytes: number) {
     const { audioContext, sounds } = this;
     const sound = { buffer: null } as Sound;
-    sounds.push(sound);
-    const pointer = sounds.length;
+    const pointer = sounds.insert(sound);
     audioContext.decodeAudioData(
       part.readBytes(bytes).slice().buffer,
       (buffer) => {
         sound.buffer = buffer;
         // console.log(buffer.duration);
-        this.taskFinish();
+        this.taskFinish(taskKinds.sound, pointer);
       },
       (err) => {
-        this.taskFinish();
-        fail(err.message);
+        sound.failure = err.message;
+        this.taskFinish(taskKinds.sound, pointer, sound.failure);
       }
     );
     this.tasksActive += 1;
     return pointer;
   }
 
-  soundPlay(part: Part, info: number) {
-    const infoView = part.memoryViewMake(info, 6 * 4);
+  soundInfo(part: Part, result: number, sound: number) {
+    const { buffer } = this.sounds.get(sound);
+    const view = part.memoryViewMake(result, 3 * 4);
+    // Browsers resample to the context rate, so that's what we know.
+    setF32(view, 0, buffer?.duration ?? 0);
+    setU32(view, 1 * 4, buffer?.sampleRate ?? 0);
+    setU32(view, 2 * 4, buffer?.numberOfChannels ?? 0);
+  }
+
+  soundPanningUpdate(play: number, panning: number, duration: number) {
+    const it = this.#soundPlayGet(play);
+    if (!it) return;
+    panning = Math.min(Math.max(panning, -1), 1);
+    paramRamp(this.audioContext, it.panner.pan, panning, duration);
+  }
+
+  soundPause(play: number, duration: number) {
+    const it = this.#soundPlayGet(play);
+    if (!it || it.paused) return;
+    it.paused = true;
+    // Buffer sources can't pause, so fade out and then hold position.
+    const { audioContext } = this;
+    const end = paramRamp(audioContext, it.gain.gain, 0, duration);
+    it.source.playbackRate.setValueAtTime(0, end);
+  }
+
+  soundPlay(part: Part, info: number, infoSize: number) {
+    // Zero fields past older layouts to get their defaults.
+    const infoView = new DataView(new ArrayBuffer(11 * 4));
+    const { buffer, byteOffset } = part.memoryViewMake(info, infoSize);
+    new Uint8Array(infoView.buffer).set(
+      new Uint8Array(buffer, byteOffset, infoSize)
+    );
     const sound = getU32(infoView, 0 * 4);
     const { audioContext, sounds } = this;
     const source = audioContext.createBufferSource();
-    source.buffer = sounds[sound - 1].buffer;
+    source.buffer = sounds.get(sound).buffer;
     // Delay.
     const delay = getF32(infoView, 1 * 4);
     const startTime = delay > 0 ? audioContext.currentTime + delay : 0;
+    // Loop.
+    if (getU8(infoView, 6 * 4)) {
+      source.loop = true;
+      source.loopStart = Math.max(getF32(infoView, 7 * 4), 0);
+      const loopEnd = getF32(infoView, 8 * 4);
+      // Match native, where an end not after the start means the sound end.
+      source.loopEnd =
+        loopEnd > source.loopStart ? loopEnd : source.buffer?.duration ?? 0;
+    }
     // Rate.
     const rate = getF32(infoView, 2 * 4);
     switch (getU32(infoView, 3 * 4)) {
@@ -738,22 +961,214 @@ class App {
         break;
     }
     // Volume.
-    let volume = getF32(infoView, 4 * 4);
-    if (getU32(infoView, 5 * 4) == 0) {
-      // Decibels.
-      volume = Math.pow(10, volume / 20);
-    }
-    if (volume == 1) {
-      source.connect(audioContext.destination);
-    } else {
-      const gainNode = audioContext.createGain();
-      gainNode.gain.value = volume;
-      source.connect(gainNode);
-      gainNode.connect(audioContext.destination);
-    }
+    const volume = volumeFromExtern(
+      getF32(infoView, 4 * 4),
+      getU32(infoView, 5 * 4)
+    );
+    const gain = audioContext.createGain();
+    gain.gain.value = volume;
+    const panner = audioContext.createStereoPanner();
+    panner.pan.value = Math.min(Math.max(getF32(infoView, 9 * 4), -1), 1);
+    // Track 0 is also what apps get when audio is unavailable.
+    const track = getU32(infoView, 10 * 4);
+    const destination = track
+      ? this.soundTracks.get(track).gain
+      : audioContext.destination;
+    source.connect(gain).connect(panner).connect(destination);
     // Play.
+    const play: SoundPlay = {
+      gain,
+      panner,
+      paused: false,
+      rate: source.playbackRate.value,
+      source,
+      volume,
+    };
+    const handle = this.soundPlays.insert(play);
+    source.onended = () => this.soundPlays.remove(handle);
     source.start(startTime);
-    return 0;
+    return handle;
+  }
+
+  // Plays that have finished ignore changes, as on native.
+  #soundPlayGet(play: number) {
+    return this.soundPlays.has(play) ? this.soundPlays.get(play) : undefined;
+  }
+
+  soundPlays = new Pool<SoundPlay>("sound play");
+
+  soundRateUpdate(play: number, rate: number, kind: number, duration: number) {
+    const it = this.#soundPlayGet(play);
+    if (!it) return;
+    const { audioContext } = this;
+    let detune = 0;
+    switch (kind) {
+      case 0:
+        detune = 100 * rate;
+        it.rate = 1;
+        break;
+      case 1:
+        it.rate = rate;
+        break;
+      default:
+        return;
+    }
+    paramRamp(audioContext, it.source.detune, detune, duration);
+    if (!it.paused) {
+      paramRamp(audioContext, it.source.playbackRate, it.rate, duration);
+    }
+  }
+
+  soundResume(play: number, duration: number) {
+    const it = this.#soundPlayGet(play);
+    if (!it || !it.paused) return;
+    it.paused = false;
+    const { audioContext } = this;
+    paramRamp(audioContext, it.source.playbackRate, it.rate, 0);
+    paramRamp(audioContext, it.gain.gain, it.volume, duration);
+  }
+
+  soundStatus(sound: number) {
+    const { buffer, failure } = this.sounds.get(sound);
+    return taskStatus(buffer, failure);
+  }
+
+  soundStop(play: number, duration: number) {
+    const it = this.#soundPlayGet(play);
+    if (!it) return;
+    const end = paramRamp(this.audioContext, it.gain.gain, 0, duration);
+    it.source.stop(end);
+  }
+
+  soundStreamDrop(stream: number) {
+    if (!stream) return;
+    this.soundStreams.remove(stream).close();
+  }
+
+  soundStreamNew(part: Part, info: number) {
+    const infoView = part.memoryViewMake(info, 4 * 4);
+    const sampleRate = getU32(infoView, 0);
+    const channels = getU32(infoView, 1 * 4);
+    const capacity = getU32(infoView, 2 * 4);
+    const track = getU32(infoView, 3 * 4);
+    if (channels < 1 || channels > 2) {
+      failBadValue(`stream channels ${channels}`);
+    }
+    if (sampleRate < 1 || sampleRate > 384000) {
+      failBadValue(`stream sample rate ${sampleRate}`);
+    }
+    if (capacity > 60 * sampleRate) {
+      failBadValue(`stream capacity ${capacity}`);
+    }
+    const { audioContext } = this;
+    const destination = track
+      ? this.soundTracks.get(track).gain
+      : audioContext.destination;
+    const stream = new SoundStream(
+      audioContext,
+      { capacity, channels, sampleRate },
+      destination
+    );
+    return this.soundStreams.insert(stream);
+  }
+
+  soundStreams = new Pool<SoundStream>("sound stream");
+
+  soundStreamSpace(stream: number) {
+    return stream ? this.soundStreams.get(stream).space() : 0;
+  }
+
+  soundStreamWrite(part: Part, stream: number, samples: number) {
+    if (!stream) return 0;
+    const it = this.soundStreams.get(stream);
+    // Copy for alignment.
+    const bytes = part.readBytes(samples, 4).slice();
+    return it.write(new Float32Array(bytes.buffer));
+  }
+
+  soundTrackDrop(track: number) {
+    if (!track) return;
+    this.soundTracks.remove(track).output.disconnect();
+  }
+
+  soundTrackNew(part: Part, info: number) {
+    const infoView = part.memoryViewMake(info, 5 * 4);
+    const { audioContext, soundTracks } = this;
+    const parent = getU32(infoView, 0);
+    const destination = parent
+      ? soundTracks.get(parent).gain
+      : audioContext.destination;
+    const effects = part.readAny(info + 3 * 4, 9 * 4, (view, offset) =>
+      soundEffectBuild(audioContext, view, offset)
+    );
+    // Chain effects after the track volume.
+    const gain = audioContext.createGain();
+    gain.gain.value = volumeFromExtern(
+      getF32(infoView, 1 * 4),
+      getU32(infoView, 2 * 4)
+    );
+    let output: AudioNode = gain;
+    for (const [effectInput, effectOutput] of effects) {
+      output.connect(effectInput);
+      output = effectOutput;
+    }
+    output.connect(destination);
+    return soundTracks.insert({ gain, output });
+  }
+
+  soundTracks = new Pool<SoundTrack>("sound track");
+
+  soundTrackVolumeUpdate(
+    track: number,
+    volume: number,
+    kind: number,
+    duration: number
+  ) {
+    if (!track || kind > 1) return;
+    const { gain } = this.soundTracks.get(track);
+    volume = volumeFromExtern(volume, kind);
+    paramRamp(this.audioContext, gain.gain, volume, duration);
+  }
+
+  soundVolumeUpdate(
+    play: number,
+    volume: number,
+    kind: number,
+    duration: number
+  ) {
+    const it = this.#soundPlayGet(play);
+    if (!it || kind > 1) return;
+    it.volume = volumeFromExtern(volume, kind);
+    if (!it.paused) {
+      paramRamp(this.audioContext, it.gain.gain, it.volume, duration);
+    }
+  }
+
+  storage: AppStorage;
+
+  statusMessageWrite(part: Part, failure: string | undefined, bytes: number) {
+    if (!failure) {
+      return 0;
+    }
+    const message = textEncoder.encode(failure);
+    const buffer = part.readBytes(bytes);
+    buffer.set(message.subarray(0, buffer.length));
+    return message.length;
+  }
+
+  storageRead(part: Part, key: number, bytes: number) {
+    const value = this.storage.read(part.readBytes(key));
+    if (!value) {
+      return 0;
+    }
+    // Report the full size so the app can retry with a bigger buffer.
+    const buffer = part.readBytes(bytes);
+    buffer.set(value.subarray(0, buffer.length));
+    return value.length;
+  }
+
+  storageWrite(part: Part, key: number, bytes: number) {
+    return this.storage.write(part.readBytes(key), part.readBytes(bytes));
   }
 
   tacaBuffer: WebGLBuffer | null = null;
@@ -772,28 +1187,49 @@ class App {
   }
 
   private tacaBufferUpdate() {
-    const { canvas } = this.config;
+    const [width, height] = this.#passSize();
     if (this.tacaBuffer) {
       const { gl } = this;
-      for (const pipeline of this.pipelines) {
+      for (const pipeline of this.pipelines.items()) {
         // This helps flip the y axis to match wgpu.
         // TODO Instead render to texture then flip the texture.
         gl.bindBuffer(gl.UNIFORM_BUFFER, this.tacaBuffer);
         const tacaBytes = new Uint8Array(pipeline.uniforms.tacaSize);
         const tacaView = new DataView(tacaBytes.buffer);
-        tacaView.setFloat32(0, canvas.width, true);
-        tacaView.setFloat32(4, canvas.height, true);
+        tacaView.setFloat32(0, width, true);
+        tacaView.setFloat32(4, height, true);
         // console.log(tacaBytes);
         gl.bufferSubData(gl.UNIFORM_BUFFER, 0, tacaBytes);
       }
     }
   }
 
+  /** Targets already drawn this frame, which later passes keep. */
+  targetsDrawn = new Set<number>();
+
   tasksActive = 0;
 
-  taskFinish() {
+  taskEvent = new DataView(new Uint32Array(3).buffer);
+  taskEventBytes = new Uint8Array(this.taskEvent.buffer);
+
+  /** Tells the app about each finished task and then about all being done. */
+  taskFinish(kind: number, handle: number, failure?: string) {
     this.tasksActive -= 1;
-    this.partsUpdate(eventTypes.tasksDone);
+    if (failure) {
+      console.error(`task ${handle} failed: ${failure}`);
+    }
+    // Skip items dropped while decoding.
+    const pool = kind == taskKinds.image ? this.textures : this.sounds;
+    if (pool.has(handle)) {
+      setU32(this.taskEvent, 0, kind);
+      setU32(this.taskEvent, 4, handle);
+      const status = failure ? taskStatuses.failed : taskStatuses.ready;
+      setU32(this.taskEvent, 8, status);
+      this.partsUpdate(failure ? eventTypes.taskFailed : eventTypes.taskDone);
+    }
+    if (this.tasksActive == 0) {
+      this.partsUpdate(eventTypes.tasksDone);
+    }
   }
 
   textAlign(x: number, y: number) {
@@ -829,7 +1265,7 @@ class App {
     let makeNew = !textureIndex;
     let texture: WebGLTexture;
     if (textureIndex) {
-      const textureInfo = textures[textureIndex - 1];
+      const textureInfo = textures.get(textureIndex);
       if (
         textureInfo.size[0] < offscreen.width ||
         textureInfo.size[1] < offscreen.height
@@ -849,10 +1285,11 @@ class App {
         texture: texture,
         usedSize: [width, height],
       };
-      if (!textureIndex) {
-        textureIndex = textures.length + 1;
+      if (textureIndex) {
+        textures.replace(textureIndex, textureInfo);
+      } else {
+        textureIndex = textures.insert(textureInfo);
       }
-      textures[textureIndex - 1] = textureInfo;
     }
     gl.bindTexture(gl.TEXTURE_2D, texture!);
     if (makeNew) {
@@ -880,14 +1317,58 @@ class App {
   textTextureText: string = "";
   texturePipeline: TexturePipeline;
 
+  textureDrop(texture: number) {
+    const { gl } = this;
+    const { target, texture: glTexture } = this.textures.remove(texture);
+    gl.deleteTexture(glTexture);
+    if (target) {
+      textureTargetDrop(gl, target);
+    }
+  }
+
   textureInfo(part: Part, result: number, texture: number) {
-    let size = this.textures[texture - 1]?.size ?? [0, 0];
+    const { textures } = this;
+    let size = textures.has(texture) ? textures.get(texture).size : [0, 0];
     const view = part.memoryViewMake(result, 2 * 4);
     setF32(view, 0, size[0]);
     setF32(view, 4, size[1]);
   }
 
-  textures: Texture[] = [];
+  textureNew(part: Part, info: number) {
+    const infoView = part.memoryViewMake(info, 5 * 4);
+    const width = getU32(infoView, 0);
+    const height = getU32(infoView, 1 * 4);
+    const format = getU32(infoView, 2 * 4);
+    const pixels = part.readBytes(info + 3 * 4);
+    return this.textures.insert(
+      textureNew(this.gl, width, height, format, pixels)
+    );
+  }
+
+  textures = new Pool<Texture>("texture");
+
+  textureStatus(texture: number) {
+    const { failure, pending } = this.textures.get(texture);
+    return taskStatus(!pending, failure);
+  }
+
+  textureTargetNew(width: number, height: number) {
+    return this.textures.insert(textureTargetNew(this.gl, width, height));
+  }
+
+  textureUpdate(
+    part: Part,
+    texture: number,
+    pixels: number,
+    x: number,
+    y: number,
+    width: number,
+    height: number
+  ) {
+    const textureInfo = this.textures.get(texture);
+    const bytes = part.readBytes(pixels);
+    textureUpdate(this.gl, textureInfo, bytes, x, y, width, height);
+  }
 
   #uniformsBuild(program: WebGLProgram): Uniforms {
     const { gl } = this;
@@ -951,6 +1432,101 @@ async function audioEnsureResumed(audioContext: AudioContext) {
   }
 }
 
+/** Gl blend state for color and alpha, or null for no blending. */
+type Blend = { alpha: BlendComponent; color: BlendComponent } | null;
+
+function blendApply(gl: WebGL2RenderingContext, blend: Blend) {
+  if (!blend) {
+    gl.disable(gl.BLEND);
+    return;
+  }
+  const { alpha, color } = blend;
+  gl.enable(gl.BLEND);
+  gl.blendFuncSeparate(color.src, color.dst, alpha.src, alpha.dst);
+  gl.blendEquationSeparate(color.op, alpha.op);
+}
+
+/** Matches native blend_state_from_extern. */
+function blendBuild(gl: WebGL2RenderingContext, info: BlendInfo): Blend {
+  const { preset } = info;
+  if (preset == 4) return null;
+  // Presets use the same factor and operation indices as custom components.
+  const over: BlendComponentInfo = [1, 5, 0];
+  const color: BlendComponentInfo =
+    preset == 0
+      ? [4, 5, 0]
+      : preset == 1
+      ? over
+      : preset == 2
+      ? [4, 1, 0]
+      : preset == 3
+      ? [6, 0, 0]
+      : preset == 5
+      ? info.color
+      : failBadValue(`blend ${preset}`);
+  const alpha = preset == 5 ? info.alpha : over;
+  return {
+    alpha: blendComponentBuild(gl, alpha),
+    color: blendComponentBuild(gl, color),
+  };
+}
+
+interface BlendComponent {
+  dst: number;
+  op: number;
+  src: number;
+}
+
+/** Extern src factor, dst factor, and operation indices in wgpu order. */
+type BlendComponentInfo = [src: number, dst: number, op: number];
+
+function blendComponentBuild(
+  gl: WebGL2RenderingContext,
+  [src, dst, op]: BlendComponentInfo
+): BlendComponent {
+  const factors = [
+    gl.ZERO,
+    gl.ONE,
+    gl.SRC_COLOR,
+    gl.ONE_MINUS_SRC_COLOR,
+    gl.SRC_ALPHA,
+    gl.ONE_MINUS_SRC_ALPHA,
+    gl.DST_COLOR,
+    gl.ONE_MINUS_DST_COLOR,
+    gl.DST_ALPHA,
+    gl.ONE_MINUS_DST_ALPHA,
+    gl.SRC_ALPHA_SATURATE,
+  ];
+  const ops = [
+    gl.FUNC_ADD,
+    gl.FUNC_SUBTRACT,
+    gl.FUNC_REVERSE_SUBTRACT,
+    gl.MIN,
+    gl.MAX,
+  ];
+  const glOp = ops[op] ?? failBadValue(`blend operation ${op}`);
+  // Min and max ignore factors, as on native.
+  if (op >= 3) return { dst: gl.ONE, op: glOp, src: gl.ONE };
+  return {
+    dst: factors[dst] ?? failBadValue(`blend factor ${dst}`),
+    op: glOp,
+    src: factors[src] ?? failBadValue(`blend factor ${src}`),
+  };
+}
+
+interface BlendInfo {
+  alpha: BlendComponentInfo;
+  color: BlendComponentInfo;
+  /** Preset index, where 5 uses the components. */
+  preset: number;
+}
+
+const blendInfoDefault: BlendInfo = {
+  alpha: [0, 0, 0],
+  color: [0, 0, 0],
+  preset: 0,
+};
+
 interface Buffers {
   // TODO images/textures
   index: Buffer;
@@ -987,32 +1563,74 @@ const eventTypes = {
   press: 3,
   release: 4,
   text: 5,
+  taskFailed: 6,
+  taskDone: 7,
+};
+
+const loadOps = {
+  auto: 0,
+  clear: 1,
+  load: 2,
+};
+
+const taskKinds = {
+  image: 0,
+  sound: 1,
+};
+
+const taskStatuses = {
+  pending: 0,
+  ready: 1,
+  failed: 2,
 };
 
+/** Wraps each host function as native host_call does. */
+function hostCallsWrap<T extends Record<string, (...args: any[]) => any>>(
+  app: App,
+  env: T
+) {
+  const wrapped: Record<string, (...args: any[]) => any> = {};
+  for (const [name, call] of Object.entries(env)) {
+    wrapped[name] = (...args) => app.hostCall(name, () => call(...args));
+  }
+  return wrapped as T;
+}
+
 async function loadApp(config: AppConfig) {
   const appData = config.code as ArrayBuffer;
   config.code = undefined;
-  // Read wasm buffers.
+  // Read wasm buffers and any other files.
   const appBytes = new Uint8Array(appData);
-  const wasmBuffers = appBytes[0] == 0x50 ? zipRead(appBytes) : [appData];
+  const { files, id, wasms } =
+    appBytes[0] == 0x50
+      ? zipRead(appBytes)
+      : {
+          files: new Map<string, Uint8Array>(),
+          id: undefined,
+          wasms: [{ name: "app.wasm", buffer: appData }],
+        };
+  const name = config.name ?? (await storageNameMake(id, wasms.at(-1)!.buffer));
+  const bundleFiles = new BundleFiles(files);
   // Instantiate extensions.
   // TODO Recursive dependencies.
-  const app = new App(config);
+  const app = new App({ ...config, name });
   const reservedExports = new Set(["init", "initialize", "start", "update"]);
   const bonusExports = {};
-  const makePart = async (buffer: ArrayBufferLike) => {
-    const part = new Part();
+  // Share one random stream across parts, as native does.
+  const randomFill = randomFillMake(config.seed);
+  const makePart = async ({ name, buffer }: WasmPart) => {
+    const part = new Part(name);
     const env = makeAppEnv(app, part);
     Object.assign(env, bonusExports);
-    const wasi = makeWasiEnv(part);
+    const wasi = makeWasiEnv(part, config.args ?? [], bundleFiles, randomFill);
     let { instance } = await WebAssembly.instantiate(buffer, {
       env,
       wasi_snapshot_preview1: wasi,
     });
-    part.init(instance);
+    part.instantiated(instance);
     return part;
   };
-  const extPromises = wasmBuffers.slice(0, -1).map(makePart);
+  const extPromises = wasms.slice(0, -1).map(makePart);
   const parts = await Promise.all(extPromises);
   // Fill in bonus exports after extensions are instantiated.
   for (const part of parts) {
@@ -1031,18 +1649,12 @@ async function loadApp(config: AppConfig) {
     );
   }
   // Build the main app part and init everything.
-  const appPart = await makePart(wasmBuffers.at(-1)!);
+  const appPart = await makePart(wasms.at(-1)!);
   parts.push(appPart);
   // Init them in order.
   for (const part of parts) {
-    const exports = part.exports;
-    if (exports._initialize) {
-      exports._initialize();
-    } else if (exports._start) {
-      exports._start();
-    }
-    if (exports.start) {
-      exports.start();
+    if (!app.partCall(part, () => part.init())) {
+      return;
     }
   }
   // Run updates.
@@ -1054,7 +1666,9 @@ async function loadApp(config: AppConfig) {
       } finally {
         app.frameEnd();
       }
-      requestAnimationFrame(update);
+      if (!app.halted) {
+        requestAnimationFrame(update);
+      }
     };
     requestAnimationFrame(update);
   }
@@ -1069,13 +1683,16 @@ async function loadAppData(code: ArrayBuffer | Promise<Response>) {
 }
 
 function makeAppEnv(app: App, part: Part) {
-  return {
+  return hostCallsWrap(app, {
     taca_bindings_apply(bindings: number) {
       app.bindingsApply(bindings);
     },
     taca_bindings_new(info: number) {
       return app.bindingsNew(part, info);
     },
+    taca_buffer_drop(buffer: number) {
+      app.bufferDrop(buffer);
+    },
     taca_buffer_new(type: number, info: number) {
       return app.bufferNew(part, type, info);
     },
@@ -1101,30 +1718,140 @@ function makeAppEnv(app: App, part: Part) {
     taca_draw(itemBegin: number, itemCount: number, instanceCount: number) {
       app.draw(itemBegin, itemCount, instanceCount);
     },
+    taca_error_last() {
+      const errorLast = app.errorLast;
+      app.errorLast = 0;
+      return errorLast;
+    },
     taca_image_decode(bytes: number) {
       return app.imageDecode(part, bytes);
     },
     taca_key_event(result: number) {
       part.memoryBytes().set(app.keyEventBytes, result);
     },
+    taca_pass_begin(info: number) {
+      const infoView = part.memoryViewMake(info, 7 * 4);
+      app.passBegin({
+        target: getU32(infoView, 0),
+        clearColor: [0, 1, 2, 3].map((i) => getF32(infoView, (1 + i) * 4)),
+        colorLoad: getU32(infoView, 5 * 4),
+        depthLoad: getU32(infoView, 6 * 4),
+      });
+    },
     taca_pipeline_apply(pipeline: number) {
       app.pipelineApply(pipeline);
     },
+    taca_pipeline_drop(pipeline: number) {
+      app.pipelineDrop(pipeline);
+    },
     taca_pipeline_new(info: number) {
-      return app.pipelineNew(part, info);
+      return app.pipelineNew(part, info, 11 * 4);
+    },
+    taca_pipeline_new2(info: number) {
+      return app.pipelineNew(part, info, 18 * 4);
     },
     taca_print(text: number) {
       console.log(part.readString(text));
     },
+    taca_shader_drop(shader: number) {
+      app.shaders.remove(shader);
+    },
     taca_shader_new(bytes: number) {
-      app.shaders.push(shaderNew(part.readBytes(bytes)));
-      return app.shaders.length;
+      return app.shaders.insert(shaderNew(part.readBytes(bytes)));
     },
     taca_sound_decode(bytes: number) {
       return app.soundDecode(part, bytes);
     },
+    taca_sound_decode_mode(bytes: number, mode: number) {
+      if (mode > 2) failBadValue(`sound decode mode ${mode}`);
+      // Browsers decode whole files, so streaming isn't an option here.
+      return app.soundDecode(part, bytes);
+    },
+    taca_sound_drop(sound: number) {
+      app.sounds.remove(sound);
+    },
+    taca_sound_info(result: number, sound: number) {
+      app.soundInfo(part, result, sound);
+    },
+    taca_sound_panning_update(
+      play: number,
+      panning: number,
+      duration: number
+    ) {
+      app.soundPanningUpdate(play, panning, duration);
+    },
+    taca_sound_pause(play: number, duration: number) {
+      app.soundPause(play, duration);
+    },
     taca_sound_play(info: number) {
-      return app.soundPlay(part, info);
+      return app.soundPlay(part, info, 6 * 4);
+    },
+    taca_sound_play2(info: number) {
+      return app.soundPlay(part, info, 11 * 4);
+    },
+    taca_sound_rate_update(
+      play: number,
+      rate: number,
+      kind: number,
+      duration: number
+    ) {
+      app.soundRateUpdate(play, rate, kind, duration);
+    },
+    taca_sound_resume(play: number, duration: number) {
+      app.soundResume(play, duration);
+    },
+    taca_sound_status(sound: number) {
+      return app.soundStatus(sound);
+    },
+    taca_sound_status_message(sound: number, bytes: number) {
+      const { failure } = app.sounds.get(sound);
+      return app.statusMessageWrite(part, failure, bytes);
+    },
+    taca_sound_stop(play: number, duration: number) {
+      app.soundStop(play, duration);
+    },
+    taca_sound_stream_drop(stream: number) {
+      app.soundStreamDrop(stream);
+    },
+    taca_sound_stream_new(info: number) {
+      return app.soundStreamNew(part, info);
+    },
+    taca_sound_stream_space(stream: number) {
+      return app.soundStreamSpace(stream);
+    },
+    taca_sound_stream_write(stream: number, samples: number) {
+      return app.soundStreamWrite(part, stream, samples);
+    },
+    taca_sound_track_drop(track: number) {
+      app.soundTrackDrop(track);
+    },
+    taca_sound_track_new(info: number) {
+      return app.soundTrackNew(part, info);
+    },
+    taca_sound_track_volume_update(
+      track: number,
+      volume: number,
+      kind: number,
+      duration: number
+    ) {
+      app.soundTrackVolumeUpdate(track, volume, kind, duration);
+    },
+    taca_sound_volume_update(
+      play: number,
+      volume: number,
+      kind: number,
+      duration: number
+    ) {
+      app.soundVolumeUpdate(play, volume, kind, duration);
+    },
+    taca_storage_read(key: number, bytes: number) {
+      return app.storageRead(part, key, bytes);
+    },
+    taca_storage_write(key: number, bytes: number) {
+      return app.storageWrite(part, key, bytes);
+    },
+    taca_task_event(result: number) {
+      part.memoryBytes().set(app.taskEventBytes, result);
     },
     taca_text_align(x: number, y: number) {
       app.textAlign(x, y);
@@ -1135,9 +1862,35 @@ function makeAppEnv(app: App, part: Part) {
     taca_text_event(result: number) {
       part.memoryBytes().set(app.textEventBytes, result);
     },
+    taca_texture_drop(texture: number) {
+      app.textureDrop(texture);
+    },
     taca_texture_info(result: number, texture: number) {
       app.textureInfo(part, result, texture);
     },
+    taca_texture_new(info: number) {
+      return app.textureNew(part, info);
+    },
+    taca_texture_status(texture: number) {
+      return app.textureStatus(texture);
+    },
+    taca_texture_status_message(texture: number, bytes: number) {
+      const { failure } = app.textures.get(texture);
+      return app.statusMessageWrite(part, failure, bytes);
+    },
+    taca_texture_target_new(width: number, height: number) {
+      return app.textureTargetNew(width, height);
+    },
+    taca_texture_update(
+      texture: number,
+      pixels: number,
+      x: number,
+      y: number,
+      width: number,
+      height: number
+    ) {
+      app.textureUpdate(part, texture, pixels, x, y, width, height);
+    },
     taca_title_update(title: number) {
       // TODO Abstract to provide callbacks for these things?
       document.title = part.readString(title);
@@ -1145,12 +1898,27 @@ function makeAppEnv(app: App, part: Part) {
     taca_window_state(result: number) {
       app.windowState(part, result);
     },
-  };
+  });
 }
 
+interface PassInfo {
+  clearColor: number[];
+  colorLoad: number;
+  depthLoad: number;
+  target: number;
+}
+
+const passInfoDefault: PassInfo = {
+  clearColor: [0, 0, 0, 1],
+  colorLoad: loadOps.auto,
+  depthLoad: loadOps.auto,
+  target: 0,
+};
+
 interface Pipeline {
   attributes: AttrInfo[];
   bindGroups: BindGroupLayout[];
+  blend: Blend;
   buffers: BufferInfo[];
   depthTest: boolean;
   program: WebGLProgram;
@@ -1158,6 +1926,7 @@ interface Pipeline {
 }
 
 interface PipelineInfo {
+  blend: BlendInfo;
   depthTest: boolean;
   fragment: ShaderInfo;
   vertex: ShaderInfo;
@@ -1165,15 +1934,37 @@ interface PipelineInfo {
   vertexBuffers: BufferInfo[];
 }
 
-function pipelineInfoDefault(info: Partial<PipelineInfo> = {}): PipelineInfo {
+/** Ramps linearly from the current value and returns the end time. */
+function paramRamp(
+  audioContext: BaseAudioContext,
+  param: AudioParam,
+  value: number,
+  duration: number
+) {
+  const now = audioContext.currentTime;
+  param.cancelScheduledValues(now);
+  if (!(duration > 0)) {
+    param.setValueAtTime(value, now);
+    return now;
+  }
+  param.setValueAtTime(param.value, now);
+  param.linearRampToValueAtTime(value, now + duration);
+  return now + duration;
+}
+
+function pipelineInfoDefault(
+  info: Partial<PipelineInfo>,
+  shaderDefault: number
+): PipelineInfo {
   const fragment: Partial<ShaderInfo> = info.fragment ?? {};
   const vertex: Partial<ShaderInfo> = info.vertex ?? {};
   fragment.entry ||= "fragment_main";
   vertex.entry ||= "vertex_main";
-  // The second `|| 1` isn't needed, but it's less risky against reorder.
-  fragment.shader ||= vertex.shader || 1;
-  vertex.shader ||= fragment.shader || 1;
+  // The second default isn't needed, but it's less risky against reorder.
+  fragment.shader ||= vertex.shader || shaderDefault;
+  vertex.shader ||= fragment.shader || shaderDefault;
   return {
+    blend: info.blend ?? blendInfoDefault,
     depthTest: info.depthTest ?? false,
     fragment: fragment as ShaderInfo,
     vertex: vertex as ShaderInfo,
@@ -1182,6 +1973,97 @@ function pipelineInfoDefault(info: Partial<PipelineInfo> = {}): PipelineInfo {
   };
 }
 
+/** Decaying noise, only roughly like kira's reverb. */
+function reverbImpulseMake(
+  audioContext: BaseAudioContext,
+  feedback: number,
+  damping: number
+) {
+  const { sampleRate } = audioContext;
+  const length = Math.ceil(sampleRate * (0.2 + 3 * feedback));
+  const impulse = audioContext.createBuffer(2, length, sampleRate);
+  for (let channel = 0; channel < 2; channel += 1) {
+    const data = impulse.getChannelData(channel);
+    for (let i = 0; i < length; i += 1) {
+      const decay = Math.pow(1 - i / length, 2 + 4 * damping);
+      data[i] = (2 * Math.random() - 1) * decay;
+    }
+  }
+  return impulse;
+}
+
+/** Approximates kira effects, with zero values keeping kira's defaults. */
+function soundEffectBuild(
+  audioContext: BaseAudioContext,
+  view: DataView,
+  offset: number
+): [input: AudioNode, output: AudioNode] {
+  const param = (index: number) => getF32(view, offset + index * 4);
+  const kind = getU32(view, offset);
+  let effect: AudioNode;
+  let mix: number;
+  switch (kind) {
+    case 0: {
+      const filter = audioContext.createBiquadFilter();
+      const mode = getU32(view, offset + 2 * 4);
+      filter.type =
+        filterTypes[mode] ?? failBadValue(`filter mode ${mode}`);
+      filter.frequency.value = param(3) || 1000;
+      // Kira resonance goes from 0 to 1, so stretch it some for q.
+      filter.Q.value = Math.SQRT1_2 + 20 * param(4);
+      effect = filter;
+      mix = 1;
+      break;
+    }
+    case 1: {
+      const convolver = audioContext.createConvolver();
+      convolver.buffer = reverbImpulseMake(
+        audioContext,
+        param(5) || 0.9,
+        param(6) || 0.1
+      );
+      effect = convolver;
+      mix = 0.5;
+      break;
+    }
+    case 2: {
+      const time = param(7) || 0.5;
+      const delay = audioContext.createDelay(Math.max(time, 1));
+      delay.delayTime.value = time;
+      const feedback = audioContext.createGain();
+      // Zero decibels is valid here, so NaN means default instead.
+      const feedbackDb = param(8);
+      feedback.gain.value = volumeFromExtern(
+        Number.isNaN(feedbackDb) ? -6 : feedbackDb,
+        0
+      );
+      delay.connect(feedback).connect(delay);
+      effect = delay;
+      mix = 0.5;
+      break;
+    }
+    default:
+      failBadValue(`sound effect kind ${kind}`);
+  }
+  mix = param(1) || mix;
+  const input = audioContext.createGain();
+  const output = audioContext.createGain();
+  const dry = audioContext.createGain();
+  dry.gain.value = 1 - mix;
+  const wet = audioContext.createGain();
+  wet.gain.value = mix;
+  input.connect(dry).connect(output);
+  input.connect(effect).connect(wet).connect(output);
+  return [input, output];
+}
+
+const filterTypes: BiquadFilterType[] = [
+  "lowpass",
+  "bandpass",
+  "highpass",
+  "notch",
+];
+
 interface ShaderInfo {
   entry: string;
   shader: number;
@@ -1189,6 +2071,24 @@ interface ShaderInfo {
 
 interface Sound {
   buffer: AudioBuffer | null;
+  failure?: string;
+}
+
+interface SoundPlay {
+  gain: GainNode;
+  panner: StereoPannerNode;
+  paused: boolean;
+  /** Playback rate factor to restore on resume. */
+  rate: number;
+  source: AudioBufferSourceNode;
+  /** Gain to restore on resume. */
+  volume: number;
+}
+
+interface SoundTrack {
+  gain: GainNode;
+  /** The last node in the chain, connected to the parent track. */
+  output: AudioNode;
 }
 
 interface Uniforms {
@@ -1199,17 +2099,46 @@ interface Uniforms {
   tacaSize: number;
 }
 
+function taskStatus(ready: unknown, failure: string | undefined) {
+  return failure
+    ? taskStatuses.failed
+    : ready
+    ? taskStatuses.ready
+    : taskStatuses.pending;
+}
+
+/** Converts to amplitude from decibels for kind 0 or amplitude for kind 1. */
+function volumeFromExtern(volume: number, kind: number) {
+  return kind == 0 ? Math.pow(10, volume / 20) : volume;
+}
+
+interface WasmPart {
+  name: string;
+  buffer: ArrayBufferLike;
+}
+
 function zipRead(bytes: Uint8Array) {
   const entries = unzipSync(bytes, {
-    // Extract only core taca files to start with. TODO Be more selective?
-    filter: (info) => /^(?:ext\/)?[^/]+\.wasm/.test(info.name),
+    // Skip dir entries, since dirs are implied by file paths.
+    filter: (info) => !info.name.endsWith("/"),
   });
   // console.log(entries);
-  const parts = Object.entries(entries)
-    .filter((it) => it[0].startsWith("ext/"))
+  const isPart = (name: string) =>
+    name == "app.wasm" || /^ext\/[^/]+\.wasm$/.test(name);
+  const idBytes = entries["app.id"];
+  const id = idBytes && textDecoder.decode(idBytes);
+  // Keep ids safe as storage names, matching native checks.
+  if (id != null && !/^(?!\.)[\w.-]{1,64}$/.test(id)) {
+    fail(`bad app id ${id}`);
+  }
+  const wasms: WasmPart[] = Object.entries(entries)
+    .filter((it) => it[0] != "app.wasm" && isPart(it[0]))
     .sort((a, b) => (a[0] > b[0] ? 1 : -1))
-    .map((it) => it[1].buffer);
-  parts.push(entries["app.wasm"].buffer);
-  // console.log(parts);
-  return parts;
+    .map(([name, bytes]) => ({ name, buffer: bytes.buffer }));
+  wasms.push({ name: "app.wasm", buffer: entries["app.wasm"].buffer });
+  const files = new Map(
+    Object.entries(entries).filter((it) => it[0] != "app.id" && !isPart(it[0]))
+  );
+  // console.log(wasms);
+  return { files, id, wasms };
 }
@@ -1,4 +1,4 @@
-import { dataViewOf, getU32 } from "./util";
+import { dataViewOf, errorCodes, getU32, HostError } from "./util";
 
 export interface AppExports {
   _initialize: (() => void) | undefined;
@@ -8,9 +8,26 @@ export interface AppExports {
 }
 
 export class Part {
+  constructor(name: string) {
+    this.name = name;
+  }
+
   exports: AppExports = undefined as any;
 
-  init(instance: WebAssembly.Instance) {
+  /** Runs any initialization and start exports. */
+  init() {
+    const { exports } = this;
+    if (exports._initialize) {
+      exports._initialize();
+    } else if (exports._start) {
+      exports._start();
+    }
+    if (exports.start) {
+      exports.start();
+    }
+  }
+
+  instantiated(instance: WebAssembly.Instance) {
     this.exports = instance.exports as any;
     this.memory = instance.exports.memory as any;
   }
@@ -36,8 +53,12 @@ export class Part {
     return this.#memoryBufferView!;
   }
 
+  name: string;
+
   memoryViewMake(ptr: number, len: number) {
-    return new DataView(this.memory.buffer, ptr, len);
+    const { buffer } = this.memory;
+    memoryCheck(buffer.byteLength, ptr, len);
+    return new DataView(buffer, ptr, len);
   }
 
   readAny<T>(
@@ -54,10 +75,12 @@ export class Part {
   readBytes(spanPtr: number, itemSize: number = 1) {
     // Can cache memory bytes when no app calls are being made.
     const memoryBytes = this.memoryBytes();
+    memoryCheck(memoryBytes.length, spanPtr, 2 * 4);
     const spanView = new DataView(memoryBytes.buffer, spanPtr, 2 * 4);
     // Wasm is explicitly little-endian.
     const contentPtr = getU32(spanView, 0);
     const contentLen = itemSize * getU32(spanView, 4);
+    memoryCheck(memoryBytes.length, contentPtr, contentLen);
     return memoryBytes.subarray(contentPtr, contentPtr + contentLen);
   }
 
@@ -73,5 +96,12 @@ export class Part {
   }
 }
 
+function memoryCheck(memoryLen: number, ptr: number, len: number) {
+  if (ptr + len > memoryLen) {
+    const message = `bad memory access: ${len} bytes at ${ptr}`;
+    throw new HostError(errorCodes.badMemory, message);
+  }
+}
+
 export const textDecoder = new TextDecoder();
 export const textEncoder = new TextEncoder();
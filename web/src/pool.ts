@@ -0,0 +1,100 @@
+import { errorCodes, failBadValue, HostError } from "./util";
+
+const indexBits = 20;
+const indexMask = (1 << indexBits) - 1;
+const generationMax = 2 ** (32 - indexBits) - 1;
+
+/**
+ * Slots addressed by handles that pack a 1-based index in the low bits and a
+ * generation in the high bits, matching the native runtime.
+ */
+export class Pool<T> {
+  constructor(kind: string) {
+    this.kind = kind;
+  }
+
+  find(predicate: (item: T) => boolean) {
+    for (const item of this.items()) {
+      if (predicate(item)) return item;
+    }
+    return undefined;
+  }
+
+  first() {
+    const { slots } = this;
+    for (let index = 0; index < slots.length; index += 1) {
+      const slot = slots[index];
+      if (slot.item !== undefined) return handleMake(index, slot.generation);
+    }
+    return 0;
+  }
+
+  #free: number[] = [];
+
+  get(handle: number) {
+    return this.#slot(handle).item ?? this.#fail(handle);
+  }
+
+  insert(item: T) {
+    const { slots } = this;
+    // Stay within index bits, counting from 1.
+    if (!this.#free.length && slots.length >= indexMask) {
+      failBadValue(`too many ${this.kind}s`);
+    }
+    const index = this.#free.pop() ?? slots.push({ generation: 0 }) - 1;
+    const slot = slots[index];
+    slot.item = item;
+    return handleMake(index, slot.generation);
+  }
+
+  has(handle: number) {
+    const slot = this.slots[(handle & indexMask) - 1];
+    return slot?.item !== undefined && slot.generation == handle >>> indexBits;
+  }
+
+  *items() {
+    for (const slot of this.slots) {
+      if (slot.item !== undefined) yield slot.item;
+    }
+  }
+
+  kind: string;
+
+  remove(handle: number) {
+    const slot = this.#slot(handle);
+    const item = slot.item ?? this.#fail(handle);
+    slot.item = undefined;
+    // Retire slots that run out of generations rather than risk aliasing.
+    if (slot.generation < generationMax) {
+      slot.generation += 1;
+      this.#free.push((handle & indexMask) - 1);
+    }
+    return item;
+  }
+
+  replace(handle: number, item: T) {
+    const slot = this.#slot(handle);
+    slot.item ?? this.#fail(handle);
+    slot.item = item;
+  }
+
+  #fail(handle: number): never {
+    const message = `bad ${this.kind} handle ${handle}`;
+    throw new HostError(errorCodes.badHandle, message);
+  }
+
+  #slot(handle: number) {
+    const slot = this.slots[(handle & indexMask) - 1];
+    if (!slot || slot.generation != handle >>> indexBits) {
+      this.#fail(handle);
+    }
+    return slot;
+  }
+
+  slots: { generation: number; item?: T }[] = [];
+}
+
+function handleMake(index: number, generation: number) {
+  // Stay unsigned for u32 handles.
+  return ((generation << indexBits) | (index + 1)) >>> 0;
+}
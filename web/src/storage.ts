@@ -0,0 +1,82 @@
+/** Total bytes of values allowed per app. */
+const storageQuota = 1 << 20;
+/** Matches native, where hex keys become file names. */
+const keySizeMax = 125;
+
+export const storageResults = { ok: 0, badKey: 1, overQuota: 2, failed: 3 };
+
+/** Names storage by app id, else by a hash of app.wasm, as native does. */
+export async function storageNameMake(
+  id: string | undefined,
+  appWasm: BufferSource
+) {
+  if (id != null) {
+    return id;
+  }
+  // Only secure contexts have subtle, and without a name, storage stays off.
+  if (!crypto.subtle) {
+    return "";
+  }
+  const hash = new Uint8Array(await crypto.subtle.digest("SHA-256", appWasm));
+  const hex = [...hash].map((b) => b.toString(16).padStart(2, "0"));
+  return `sha256-${hex.join("")}`;
+}
+
+/** Key value storage persisted per app in local storage. */
+export class AppStorage {
+  constructor(name: string) {
+    this.prefix = name ? `taca/apps/${name}/` : "";
+  }
+
+  #keyName(key: Uint8Array) {
+    if (!this.prefix || !key.length || key.length > keySizeMax) {
+      return null;
+    }
+    // Hex keeps arbitrary keys safe and distinct.
+    const hex = [...key].map((b) => b.toString(16).padStart(2, "0"));
+    return this.prefix + hex.join("");
+  }
+
+  prefix: string;
+
+  read(key: Uint8Array) {
+    const keyName = this.#keyName(key);
+    const value = keyName && localStorage.getItem(keyName);
+    // Values are stored one byte per char.
+    return value ? Uint8Array.from(value, (c) => c.charCodeAt(0)) : null;
+  }
+
+  #usedExcept(keyName: string) {
+    let used = 0;
+    for (let i = 0; i < localStorage.length; i += 1) {
+      const name = localStorage.key(i)!;
+      if (name.startsWith(this.prefix) && name != keyName) {
+        used += localStorage.getItem(name)!.length;
+      }
+    }
+    return used;
+  }
+
+  /** Writes the value for the key, where empty values remove the key. */
+  write(key: Uint8Array, bytes: Uint8Array) {
+    const keyName = this.#keyName(key);
+    if (!keyName) {
+      return storageResults.badKey;
+    }
+    if (!bytes.length) {
+      localStorage.removeItem(keyName);
+      return storageResults.ok;
+    }
+    if (this.#usedExcept(keyName) + bytes.length > storageQuota) {
+      return storageResults.overQuota;
+    }
+    try {
+      const value = Array.from(bytes, (b) => String.fromCharCode(b)).join("");
+      localStorage.setItem(keyName, value);
+      return storageResults.ok;
+    } catch (error) {
+      console.error(error);
+      return storageResults.failed;
+    }
+  }
+}
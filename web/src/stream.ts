@@ -0,0 +1,101 @@
+/**
+ * Ring buffer of app-written frames played through a script processor,
+ * resampling linearly to the context rate, as on native.
+ */
+export class SoundStream {
+  constructor(
+    audioContext: BaseAudioContext,
+    info: SoundStreamInfo,
+    destination: AudioNode
+  ) {
+    const { channels, sampleRate } = info;
+    const capacity = info.capacity || Math.floor(sampleRate / 4);
+    this.channels = channels;
+    this.ring = new Float32Array(2 * capacity);
+    this.step = sampleRate / audioContext.sampleRate;
+    const processor = audioContext.createScriptProcessor(1024, 0, 2);
+    processor.onaudioprocess = (event) => this.#process(event.outputBuffer);
+    processor.connect(destination);
+    this.processor = processor;
+  }
+
+  channels: number;
+
+  close() {
+    this.closed = true;
+  }
+
+  closed = false;
+
+  #frameNext() {
+    const { frames, ring } = this;
+    frames.copyWithin(0, 2);
+    if (!this.count) {
+      if (this.closed) {
+        // Play out everything written before disconnecting.
+        this.processor.disconnect();
+      }
+      frames.fill(0, 2);
+      return;
+    }
+    frames[2] = ring[2 * this.start];
+    frames[3] = ring[2 * this.start + 1];
+    this.start = (this.start + 1) % (ring.length / 2);
+    this.count -= 1;
+  }
+
+  count = 0;
+  fraction = 0;
+  /** Left and right before and after the current position. */
+  frames = new Float32Array(4);
+
+  #process(output: AudioBuffer) {
+    const { frames } = this;
+    const left = output.getChannelData(0);
+    const right = output.getChannelData(1);
+    for (let i = 0; i < output.length; i += 1) {
+      this.fraction += this.step;
+      while (this.fraction >= 1) {
+        this.fraction -= 1;
+        this.#frameNext();
+      }
+      const { fraction } = this;
+      left[i] = frames[0] + (frames[2] - frames[0]) * fraction;
+      right[i] = frames[1] + (frames[3] - frames[1]) * fraction;
+    }
+  }
+
+  processor: ScriptProcessorNode;
+  ring: Float32Array;
+
+  space() {
+    return this.ring.length / 2 - this.count;
+  }
+
+  start = 0;
+  step: number;
+
+  /** Writes interleaved samples as far as they fit, returning frames. */
+  write(samples: Float32Array) {
+    const { channels, ring } = this;
+    const capacity = ring.length / 2;
+    const count = Math.min(
+      Math.floor(samples.length / channels),
+      this.space()
+    );
+    for (let i = 0; i < count; i += 1) {
+      const index = 2 * ((this.start + this.count + i) % capacity);
+      const left = samples[i * channels];
+      ring[index] = left;
+      ring[index + 1] = channels == 2 ? samples[i * channels + 1] : left;
+    }
+    this.count += count;
+    return count;
+  }
+}
+
+export interface SoundStreamInfo {
+  capacity: number;
+  channels: number;
+  sampleRate: number;
+}
@@ -14,10 +14,33 @@ export function getU8(view: DataView, byteOffset: number) {
   return view.getUint8(byteOffset);
 }
 
+/** Error codes as seen by apps, matching native ErrorCode. */
+export const errorCodes = {
+  none: 0,
+  badHandle: 1,
+  badMemory: 2,
+  badValue: 3,
+  notReady: 4,
+};
+
 export function fail(message?: string | null): never {
   throw Error(message ?? undefined);
 }
 
+export function failBadValue(message: string): never {
+  throw new HostError(errorCodes.badValue, `bad value: ${message}`);
+}
+
+/** Bad app input, reported through taca_error_last rather than halting. */
+export class HostError extends Error {
+  constructor(code: number, message: string) {
+    super(message);
+    this.code = code;
+  }
+
+  code: number;
+}
+
 export function setF32(view: DataView, byteOffset: number, value: number) {
   return view.setFloat32(byteOffset, value, true);
 }
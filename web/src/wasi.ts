@@ -1,36 +1,308 @@
-import { Part } from "./part";
-import { fail, getU32, setU16, setU32, setU64, setU8 } from "./util";
+import { Part, textDecoder, textEncoder } from "./part";
+import { getU32, setU16, setU32, setU64, setU8 } from "./util";
 
-export function makeWasiEnv(part: Part) {
+const errno = {
+  badf: 8,
+  inval: 28,
+  isdir: 31,
+  noent: 44,
+  notdir: 54,
+  rofs: 69,
+  spipe: 70,
+  notcapable: 76,
+};
+
+const fileTypes = { characterDevice: 2, directory: 3, regularFile: 4 };
+
+export class ExitError extends Error {
+  constructor(code: number) {
+    super(`exit ${code}`);
+    this.code = code;
+  }
+
+  code: number;
+}
+
+/** Fd of the preopened bundle root. */
+const fdRoot = 3;
+
+// Read, seek, tell, advise, path_open, readdir, path_filestat_get, filestat_get
+const rightsReadOnly = 0x2460a6n;
+
+type OpenFile = { path: string; pos?: number };
+
+/** Read-only bundle entries other than wasm parts, preopened as "/". */
+export class BundleFiles {
+  constructor(files: Map<string, Uint8Array>) {
+    this.files = new Map([...files].sort((a, b) => (a[0] > b[0] ? 1 : -1)));
+  }
+
+  dirEntries(dir: string) {
+    const prefix = dirPrefix(dir);
+    const entries = [] as [string, boolean][];
+    for (const name of this.files.keys()) {
+      if (!name.startsWith(prefix)) {
+        continue;
+      }
+      const rest = name.slice(prefix.length);
+      const slash = rest.indexOf("/");
+      const entry: [string, boolean] =
+        slash < 0 ? [rest, false] : [rest.slice(0, slash), true];
+      // Sorting keeps each dir's contents together.
+      const last = entries.at(-1);
+      if (!(last && last[0] == entry[0] && last[1] == entry[1])) {
+        entries.push(entry);
+      }
+    }
+    return entries;
+  }
+
+  dirPath(fd: number) {
+    const file = this.fds.get(fd);
+    if (!file) throw errno.badf;
+    if (file.pos != null) throw errno.notdir;
+    return file.path;
+  }
+
+  fds = new Map<number, OpenFile>([[fdRoot, { path: "" }]]);
+
+  fdNext = fdRoot + 1;
+
+  files: Map<string, Uint8Array>;
+
+  isDir(path: string) {
+    const prefix = dirPrefix(path);
+    return !path || [...this.files.keys()].some((it) => it.startsWith(prefix));
+  }
+
+  open(dirFd: number, path: string, oflags: number) {
+    path = pathResolve(this.dirPath(dirFd), path);
+    let file: OpenFile;
+    if (this.files.has(path)) {
+      if (oflags & 2) throw errno.notdir; // O_DIRECTORY
+      file = { path, pos: 0 };
+    } else if (this.isDir(path)) {
+      file = { path };
+    } else {
+      throw errno.noent;
+    }
+    const fd = this.fdNext++;
+    this.fds.set(fd, file);
+    return fd;
+  }
+
+  stat(path: string) {
+    const bytes = this.files.get(path);
+    if (bytes) return { fileType: fileTypes.regularFile, size: bytes.length };
+    if (this.isDir(path)) return { fileType: fileTypes.directory, size: 0 };
+    throw errno.noent;
+  }
+}
+
+function dirPrefix(dir: string) {
+  return dir ? `${dir}/` : "";
+}
+
+/** Joins a relative path to a dir, without escaping the root. */
+function pathResolve(dir: string, path: string) {
+  const parts = dir.split("/").filter((it) => it);
+  for (const part of path.split("/")) {
+    if (part == "..") {
+      if (!parts.length) throw errno.notcapable;
+      parts.pop();
+    } else if (part && part != ".") {
+      parts.push(part);
+    }
+  }
+  return parts.join("/");
+}
+
+/** Returns any thrown errno, so file functions can throw to fail. */
+function errnoCatch(action: () => void) {
+  try {
+    action();
+    return 0;
+  } catch (error) {
+    if (typeof error == "number") return error;
+    throw error;
+  }
+}
+
+export function makeWasiEnv(
+  part: Part,
+  args: string[],
+  files: BundleFiles,
+  randomFill: (bytes: Uint8Array) => void
+) {
   const outs = [] as string[];
+  const argBytes = args.map((arg) => textEncoder.encode(`${arg}\0`));
+  const fileStatWrite = (
+    filestat: number,
+    stat: { fileType: number; size: number }
+  ) => {
+    const view = part.memoryViewMake(filestat, 64);
+    for (let i = 0; i < 64; i += 8) {
+      setU64(view, i, 0n);
+    }
+    setU8(view, 16, stat.fileType);
+    setU64(view, 24, 1n); // Link count.
+    setU64(view, 32, BigInt(stat.size));
+  };
+  const readPath = (path: number, pathLen: number) =>
+    textDecoder.decode(part.memoryBytes().subarray(path, path + pathLen));
   return {
-    args_get() {
+    args_get(argv: number, argvBuf: number) {
+      const memoryBytes = part.memoryBytes();
+      const argvView = part.memoryViewMake(argv, 4 * argBytes.length);
+      let argPtr = argvBuf;
+      argBytes.forEach((bytes, i) => {
+        setU32(argvView, 4 * i, argPtr);
+        memoryBytes.set(bytes, argPtr);
+        argPtr += bytes.length;
+      });
       return 0;
     },
     args_sizes_get(argvSize: number, argvBufSize: number) {
-      setU32(part.memoryViewMake(argvSize, 4), 0, 0);
-      setU32(part.memoryViewMake(argvBufSize, 4), 0, 0);
+      const bufSize = argBytes.reduce((sum, bytes) => sum + bytes.length, 0);
+      setU32(part.memoryViewMake(argvSize, 4), 0, argBytes.length);
+      setU32(part.memoryViewMake(argvBufSize, 4), 0, bufSize);
       return 0;
     },
-    fd_close() {
+    clock_res_get(clockId: number, resolution: number) {
+      if (clockId > 3) {
+        return 28; // EINVAL
+      }
+      // Browsers may coarsen timers, but claim microseconds.
+      setU64(part.memoryViewMake(resolution, 8), 0, 1000n);
+      return 0;
+    },
+    clock_time_get(clockId: number, precision: bigint, time: number) {
+      let nanos: bigint;
+      if (clockId == 0) {
+        nanos = BigInt(Date.now()) * 1000000n;
+      } else if (clockId <= 3) {
+        // Cpu time isn't tracked separately, so treat it as monotonic.
+        nanos = BigInt(Math.round(performance.now() * 1e6));
+      } else {
+        return 28; // EINVAL
+      }
+      setU64(part.memoryViewMake(time, 8), 0, nanos);
+      return 0;
+    },
+    fd_close(fd: number) {
+      files.fds.delete(fd);
       return 0;
     },
     fd_fdstat_get(fd: number, fdstat: number) {
-      // Ignore fd for now. Just presume character device.
+      // Presume character device for anything not a bundle file.
+      const file = files.fds.get(fd);
+      let fileType = fileTypes.characterDevice;
+      let rights = 0n;
+      if (file) {
+        fileType = file.pos == null ? fileTypes.directory : fileTypes.regularFile;
+        rights = rightsReadOnly;
+      }
       const view = part.memoryViewMake(fdstat, 24);
-      setU8(view, 0, 2); // 2 is for character device.
+      setU8(view, 0, fileType);
       setU8(view, 1, 0); // Just filler, but zero anyway.
       setU16(view, 2, 0); // Flags.
       setU32(view, 4, 0); // Also filler.
-      setU64(view, 8, 0n); // Rights base.
-      setU64(view, 16, 0n); // Rights inheriting.
+      setU64(view, 8, rights); // Rights base.
+      setU64(view, 16, rights); // Rights inheriting.
+      return 0;
+    },
+    fd_filestat_get(fd: number, filestat: number) {
+      return errnoCatch(() => {
+        const file = files.fds.get(fd);
+        if (!file) throw errno.badf;
+        fileStatWrite(filestat, files.stat(file.path));
+      });
+    },
+    fd_prestat_dir_name(fd: number, path: number, pathLen: number) {
+      if (fd != fdRoot) return errno.badf;
+      part.memoryBytes().set(textEncoder.encode("/").subarray(0, pathLen), path);
+      return 0;
+    },
+    fd_prestat_get(fd: number, prestat: number) {
+      // Only the bundle root is preopened.
+      if (fd != fdRoot) return errno.badf;
+      const view = part.memoryViewMake(prestat, 8);
+      setU32(view, 0, 0); // Dir tag and filler.
+      setU32(view, 4, 1); // Name length.
+      return 0;
+    },
+    fd_read(fd: number, iovec: number, len: number, nread: number) {
+      let total = 0;
+      const result = errnoCatch(() => {
+        // Stdin is always empty.
+        if (fd == 0) return;
+        const file = files.fds.get(fd);
+        if (!file) throw errno.badf;
+        if (file.pos == null) throw errno.isdir;
+        const bytes = files.files.get(file.path)!;
+        const memoryBytes = part.memoryBytes();
+        for (let i = 0; i < len; i += 1) {
+          const view = part.memoryViewMake(iovec + 8 * i, 8);
+          const [buf, size] = [getU32(view, 0), getU32(view, 4)];
+          const chunk = bytes.subarray(file.pos, file.pos + size);
+          memoryBytes.set(chunk, buf);
+          file.pos += chunk.length;
+          total += chunk.length;
+          if (chunk.length < size) break;
+        }
+      });
+      if (result) return result;
+      setU32(part.memoryViewMake(nread, 4), 0, total);
       return 0;
     },
+    fd_readdir(
+      fd: number,
+      buf: number,
+      bufLen: number,
+      cookie: bigint,
+      bufUsed: number
+    ) {
+      return errnoCatch(() => {
+        const entries = files.dirEntries(files.dirPath(fd));
+        // Each dirent is next cookie, inode, name length, and file type, then
+        // the name. Fill the whole buffer even if the last entry gets cut off,
+        // which tells the caller to retry with more space.
+        const chunks = [] as Uint8Array[];
+        let size = 0;
+        for (let i = Number(cookie); i < entries.length && size < bufLen; i += 1) {
+          const [name, isDir] = entries[i];
+          const nameBytes = textEncoder.encode(name);
+          const dirent = new Uint8Array(24 + nameBytes.length);
+          const view = new DataView(dirent.buffer);
+          setU64(view, 0, BigInt(i + 1));
+          setU32(view, 16, nameBytes.length);
+          setU8(view, 20, isDir ? fileTypes.directory : fileTypes.regularFile);
+          dirent.set(nameBytes, 24);
+          chunks.push(dirent);
+          size += dirent.length;
+        }
+        const bytes = new Uint8Array(size);
+        chunks.reduce((offset, chunk) => {
+          bytes.set(chunk, offset);
+          return offset + chunk.length;
+        }, 0);
+        const used = Math.min(size, bufLen);
+        part.memoryBytes().set(bytes.subarray(0, used), buf);
+        setU32(part.memoryViewMake(bufUsed, 4), 0, used);
+      });
+    },
     fd_seek(fd: number, fileDelta: bigint, whence: number, newOffset: number) {
-      setU64(part.memoryViewMake(newOffset, 8), 0, 0n);
-      // Claim failure.
-      // See https://github.com/ziglang/zig/blob/4d81e8ee915c3e012131cf90ed87cc8c6a01a934/stage1/wasi.c#L998
-      return 29;
+      const newOffsetView = part.memoryViewMake(newOffset, 8);
+      setU64(newOffsetView, 0, 0n);
+      const file = files.fds.get(fd);
+      if (file?.pos == null) return errno.spipe;
+      const base = [0, file.pos, files.files.get(file.path)!.length][whence];
+      if (base == null) return errno.inval;
+      const pos = base + Number(BigInt.asIntN(64, fileDelta));
+      if (pos < 0) return errno.inval;
+      file.pos = pos;
+      setU64(newOffsetView, 0, BigInt(pos));
+      return 0;
     },
     fd_write(fd: number, iovec: number, len: number, nwritten: number) {
       let total = 0;
@@ -53,13 +325,66 @@ export function makeWasiEnv(part: Part) {
       outs[fd] = lines.at(-1)!;
       return total;
     },
-    proc_exit(code: number) {
-      // We could also mark some exit state in the app, but meh.
-      fail(`proc_exit(${code})`);
+    path_filestat_get(
+      fd: number,
+      flags: number,
+      path: number,
+      pathLen: number,
+      filestat: number
+    ) {
+      return errnoCatch(() => {
+        const dir = files.dirPath(fd);
+        const full = pathResolve(dir, readPath(path, pathLen));
+        fileStatWrite(filestat, files.stat(full));
+      });
+    },
+    path_open(
+      fd: number,
+      dirflags: number,
+      path: number,
+      pathLen: number,
+      oflags: number,
+      rightsBase: bigint,
+      rightsInheriting: bigint,
+      fdflags: number,
+      openedFd: number
+    ) {
+      // Creat, excl, trunc, or fd_write rights mean writing.
+      if (oflags & (1 | 4 | 8) || rightsBase & (1n << 6n)) return errno.rofs;
+      return errnoCatch(() => {
+        const opened = files.open(fd, readPath(path, pathLen), oflags);
+        setU32(part.memoryViewMake(openedFd, 4), 0, opened);
+      });
+    },
+    proc_exit(code: number): never {
+      // Unwinds out of the app for the runtime to handle.
+      throw new ExitError(code);
     },
     random_get(buf: number, bufLen: number) {
-      // Claim failure.
-      return 29;
+      randomFill(part.memoryBytes().subarray(buf, buf + bufLen));
+      return 0;
     },
   };
 }
+
+/** Fills from system entropy unless seeded, where the seed is taken as u32. */
+export function randomFillMake(seed?: number) {
+  if (seed == null) {
+    return (bytes: Uint8Array) => {
+      // Browsers limit each call to 64 KiB.
+      for (let i = 0; i < bytes.length; i += 0x10000) {
+        crypto.getRandomValues(bytes.subarray(i, i + 0x10000));
+      }
+    };
+  }
+  // Mulberry32, which is small and plenty for replays.
+  let state = seed >>> 0;
+  return (bytes: Uint8Array) => {
+    for (let i = 0; i < bytes.length; i += 1) {
+      state = (state + 0x6d2b79f5) | 0;
+      let t = Math.imul(state ^ (state >>> 15), state | 1);
+      t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+      bytes[i] = (t ^ (t >>> 14)) & 0xff;
+    }
+  };
+}